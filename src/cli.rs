@@ -1,3 +1,5 @@
+use crate::compare::CompareMode;
+use crate::report::ReportFormat;
 use clap::{crate_authors, crate_version, App, AppSettings, Arg, SubCommand};
 
 pub fn init() -> App<'static, 'static> {
@@ -14,6 +16,10 @@ pub fn init() -> App<'static, 'static> {
                          .help("Path to problem directory")
                          .default_value(".")
                          .index(1))
+                    .arg(Arg::with_name("TESTS")
+                         .help("Names of the test cases to run, such as \"1\" for test/1.in or \"custom/edge\" for test/custom/edge.in. Glob patterns such as \"custom*\" select every test case with a matching name. Runs every test case if none are given")
+                         .multiple(true)
+                         .index(2))
                     .arg(Arg::with_name("file")
                          .short("f")
                          .long("file")
@@ -27,14 +33,227 @@ pub fn init() -> App<'static, 'static> {
                     .arg(Arg::with_name("time")
                          .short("t")
                          .long("time")
-                         .help("Display how long each test case took to execute"))
+                         .help("Display the wall-clock time of each test case along with its CPU time. This includes starting the program and may be affected by other programs running on the machine"))
+                    .arg(Arg::with_name("time-limit")
+                         .long("time-limit")
+                         .takes_value(true)
+                         .value_name("SECONDS")
+                         .help("Reports a test case as exceeding the time limit once it has used this much CPU time, or on Windows, once it has run for this long. Defaults to the CPU time limit of the problem on Kattis if known, otherwise 10 seconds"))
+                    .arg(Arg::with_name("compile-timeout")
+                         .long("compile-timeout")
+                         .takes_value(true)
+                         .value_name("SECONDS")
+                         .default_value("60")
+                         .help("Stops compilation if it has not finished after this many seconds"))
+                    .arg(Arg::with_name("memory-limit")
+                         .long("memory-limit")
+                         .takes_value(true)
+                         .value_name("MB")
+                         .help("Limits the memory of the solution, which fails if its peak memory usage goes above it (Unix only). Defaults to the memory limit of the profile if it sets one, otherwise to that of the problem on Kattis if known. Use \"unlimited\" to remove the limit"))
+                    .arg(Arg::with_name("stack-limit")
+                         .long("stack-limit")
+                         .takes_value(true)
+                         .value_name("MB")
+                         .help("Limits the stack size of the solution (Unix only). Like on Kattis, the stack is unlimited by default"))
+                    .arg(Arg::with_name("process-limit")
+                         .long("process-limit")
+                         .takes_value(true)
+                         .value_name("COUNT")
+                         .help("Limits the number of processes and threads (Unix only). Note that the operating system counts every process run by your user towards this limit"))
+                    .arg(Arg::with_name("file-size-limit")
+                         .long("file-size-limit")
+                         .takes_value(true)
+                         .value_name("MB")
+                         .help("Limits the size of files written by the solution (Unix only)"))
+                    .arg(Arg::with_name("output-limit")
+                         .long("output-limit")
+                         .takes_value(true)
+                         .value_name("MB")
+                         .help("Reports a test case as exceeding the output limit if the solution writes more than this to stdout. Defaults to the output limit of the problem if known, otherwise 8 MB like on Kattis"))
+                    .arg(Arg::with_name("compare")
+                         .long("compare")
+                         .takes_value(true)
+                         .value_name("MODE")
+                         .possible_values(CompareMode::NAMES)
+                         .help("How the output is compared to the answer. \"exact\" compares byte for byte, \"lines\" ignores whitespace at the ends of lines, \"tokens\" only compares the whitespace-separated tokens like Kattis does, and \"case-insensitive\" does the same while ignoring case. An output that only differs in whitespace gives a presentation warning rather than a failure. Defaults to \"default_compare_mode\" in kitty.yml, or \"lines\" if it is not set"))
+                    .arg(Arg::with_name("float-tolerance")
+                         .long("float-tolerance")
+                         .takes_value(true)
+                         .value_name("EPS")
+                         .help("Accepts numbers in the output if their absolute or relative error is at most EPS. Other tokens must match exactly, and whitespace is ignored. Can also be set per problem with \"validator_flags: float_tolerance EPS\" in problem.yaml"))
+                    .arg(Arg::with_name("float-abs")
+                         .long("float-abs")
+                         .takes_value(true)
+                         .value_name("EPS")
+                         .help("Like --float-tolerance, but only sets the accepted absolute error"))
+                    .arg(Arg::with_name("float-rel")
+                         .long("float-rel")
+                         .takes_value(true)
+                         .value_name("EPS")
+                         .help("Like --float-tolerance, but only sets the accepted relative error"))
+                    .arg(Arg::with_name("validator")
+                         .long("validator")
+                         .takes_value(true)
+                         .value_name("VALIDATOR")
+                         .help("Checks the output with a custom output validator instead of comparing it to the answer. Give either a path relative to the problem directory or a command. The validator is run like on Kattis, and must exit with code 42 to accept and 43 to reject. Can also be set per problem with \"validator\" in problem.yaml"))
+                    .arg(Arg::with_name("interactor")
+                         .long("interactor")
+                         .takes_value(true)
+                         .value_name("INTERACTOR")
+                         .conflicts_with("validator")
+                         .help("Tests an interactive problem by running the solution with its stdin and stdout connected to an interactor. Give either a path relative to the problem directory or a command. The interactor is run like on Kattis, and must exit with code 42 to accept and 43 to reject. Can also be set per problem with \"interactor\" in problem.yaml"))
+                    .arg(Arg::with_name("transcript")
+                         .long("transcript")
+                         .help("Saves the exchange between the solution and the interactor next to each test input as <test>.transcript"))
+                    .arg(Arg::with_name("save-output")
+                         .long("save-output")
+                         .help("Saves the output of test cases that have no .ans file as .kitty/output/<test>.out in the problem directory instead of printing it"))
+                    .arg(Arg::with_name("side-by-side")
+                         .long("side-by-side")
+                         .help("Shows the difference between the expected and the actual output of failed test cases in two columns instead of one"))
+                    .arg(Arg::with_name("jobs")
+                         .short("j")
+                         .long("jobs")
+                         .takes_value(true)
+                         .value_name("N")
+                         .default_value("1")
+                         .help("Number of test cases to run at the same time. Use 0 to run one test case per CPU core. Running tests in parallel may make each of them slower, so use --serial when timing matters"))
+                    .arg(Arg::with_name("serial")
+                         .long("serial")
+                         .help("Runs one test case at a time regardless of --jobs, which gives the most accurate timing"))
+                    .arg(Arg::with_name("report")
+                         .long("report")
+                         .takes_value(true)
+                         .value_name("FORMAT")
+                         .possible_values(ReportFormat::NAMES)
+                         .help("Writes a machine-readable report of the compilation and every test case. Unless --report-file is given, the report replaces the usual output"))
+                    .arg(Arg::with_name("report-file")
+                         .long("report-file")
+                         .takes_value(true)
+                         .value_name("PATH")
+                         .requires("report")
+                         .help("Writes the report to this file instead of stdout"))
+                    .arg(Arg::with_name("fail-fast")
+                         .long("fail-fast")
+                         .help("Stops after the first test case that fails"))
+                    .arg(Arg::with_name("failed")
+                         .long("failed")
+                         .help("Only runs the test cases that failed the last time they were run, or all of them if none did"))
                     .arg(Arg::with_name("fetch")
                          .long("fetch")
                          .help("If the test folder does not exist, download the test files from Kattis"))
                     .arg(Arg::with_name("watch")
                          .short("w")
                          .long("watch")
-                         .help("Re-runs tests every time the source file changes"))
+                         .help("Re-runs tests every time a file in the problem directory changes, such as the source file or a test case. Build output and editor backups are ignored, and test cases that failed last time run first"))
+                    .arg(Arg::with_name("profile")
+                         .long("profile")
+                         .takes_value(true)
+                         .value_name("NAME")
+                         .help("Compiles and runs the solution with the commands of one of the profiles of its language in kitty.yml, such as debug or sanitize, instead of the default profile"))
+                    .arg(Arg::with_name("warnings")
+                         .long("warnings")
+                         .help("Shows the warnings of the compiler when the program compiles. Errors are always shown"))
+                    .arg(Arg::with_name("quickfix")
+                         .long("quickfix")
+                         .help("Prints compiler errors and warnings as file:line:column: severity: message, which Vim's quickfix list and Emacs' compilation mode can read, instead of with the lines of code they refer to"))
+                    .arg(Arg::with_name("keep-scratch")
+                         .long("keep-scratch")
+                         .help("Keeps the temporary working directory of each failed test case for inspection instead of removing it. Every test case runs in a new, empty working directory"))
+                    .arg(Arg::with_name("sandbox")
+                         .long("sandbox")
+                         .help("Runs the solution in a sandbox where it cannot reach the network and can only write to its working directory. System calls that could escape the sandbox end the program with a run-time error. Only supported on Linux 5.12 or later with unprivileged user namespaces"))
+                    .arg(Arg::with_name("rebuild")
+                         .long("rebuild")
+                         .help("Compiles the program even if the files in the problem directory, the compile command and the language are the same as the last time it compiled"))
+                    .arg(Arg::with_name("bless")
+                         .long("bless")
+                         .conflicts_with_all(&["watch", "report", "interactor"])
+                         .help("Runs a trusted solution, such as a brute force given with --file, and saves its output as the answer of each test case. The limits of the problem do not apply, only those given as options. Asks before overwriting the answers of official samples"))
+                    .arg(Arg::with_name("yes")
+                         .short("y")
+                         .long("yes")
+                         .requires("bless")
+                         .help("Overwrites the answers of official samples with --bless without asking"))
+                   )
+        .subcommand(SubCommand::with_name("tests")
+                    .about("Manages the test cases of a problem")
+                    .after_help("Custom tests are stored in test/custom, apart from the official samples, so fetching the samples again never touches them. They are named custom/<NAME> when running and listing tests.")
+                    .setting(AppSettings::DisableVersion)
+                    .setting(AppSettings::SubcommandRequiredElseHelp)
+                    .arg(Arg::with_name("path")
+                         .short("p")
+                         .long("path")
+                         .takes_value(true)
+                         .value_name("PATH")
+                         .global(true)
+                         .help("Path to problem directory [default: .]"))
+                    .subcommand(SubCommand::with_name("add")
+                                .about("Adds a custom test case, reading the input and answer from files, stdin or your editor")
+                                .arg(Arg::with_name("NAME")
+                                     .help("Name of the new test case. Defaults to the number after the highest numbered custom test")
+                                     .index(1))
+                                .arg(Arg::with_name("in")
+                                     .long("in")
+                                     .takes_value(true)
+                                     .value_name("FILE")
+                                     .help("File to read the input from. Use \"-\" to read it from stdin. If not given, $EDITOR is opened to write it"))
+                                .arg(Arg::with_name("ans")
+                                     .long("ans")
+                                     .takes_value(true)
+                                     .value_name("FILE")
+                                     .help("File to read the answer from. Use \"-\" to read it from stdin. If not given, $EDITOR is opened to write it"))
+                                .arg(Arg::with_name("no-ans")
+                                     .long("no-ans")
+                                     .conflicts_with("ans")
+                                     .help("Only adds the input, e.g. to generate the answer later with kitty test --bless"))
+                               )
+                    .subcommand(SubCommand::with_name("list")
+                                .about("Lists the test cases along with their sizes and whether they are official samples or custom tests")
+                               )
+                    .subcommand(SubCommand::with_name("show")
+                                .about("Shows the input and answer of a test case, using $PAGER if it is set")
+                                .arg(Arg::with_name("TEST")
+                                     .help("Name of the test case as shown by kitty tests list")
+                                     .required(true)
+                                     .index(1))
+                               )
+                    .subcommand(SubCommand::with_name("rm")
+                                .about("Removes test cases")
+                                .arg(Arg::with_name("TESTS")
+                                     .help("Names of the test cases as shown by kitty tests list")
+                                     .required(true)
+                                     .multiple(true)
+                                     .index(1))
+                                .arg(Arg::with_name("yes")
+                                     .short("y")
+                                     .long("yes")
+                                     .help("Removes official samples without asking"))
+                               )
+                    .subcommand(SubCommand::with_name("rename")
+                                .about("Renames a custom test case")
+                                .arg(Arg::with_name("OLD")
+                                     .help("Current name of the test case")
+                                     .required(true)
+                                     .index(1))
+                                .arg(Arg::with_name("NEW")
+                                     .help("New name of the test case")
+                                     .required(true)
+                                     .index(2))
+                               )
+                   )
+        .subcommand(SubCommand::with_name("clean")
+                    .about("Removes the compiled programs and other build output of a problem")
+                    .after_help("Build output is written to a .kitty folder in the problem directory, or to kitty's cache directory if build_location is set to cache in kitty.yml.")
+                    .setting(AppSettings::DisableVersion)
+                    .arg(Arg::with_name("PATH")
+                         .help("Path to problem directory")
+                         .default_value(".")
+                         .index(1))
+                    .arg(Arg::with_name("all")
+                         .short("a")
+                         .long("all")
+                         .help("Removes the build output of every problem in PATH and its subfolders, and everything in kitty's build cache"))
                    )
         .subcommand(SubCommand::with_name("get")
                     .about("Fetches a problem from Kattis by creating a directory of the same name and downloading the official test cases")
@@ -0,0 +1,59 @@
+use crate::config::{self, Config};
+use crate::problem::{Problem, STATE_DIR_NAME};
+use crate::StdErr;
+use clap::ArgMatches;
+use colored::Colorize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+pub async fn clean(cmd: &ArgMatches<'_>) -> Result<(), StdErr> {
+    // We can unwrap because the argument has a default value.
+    let path = Problem::get_path(cmd.value_of("PATH").unwrap())?;
+
+    let build_dirs = if cmd.is_present("all") {
+        let mut dirs = vec![Config::build_cache_path()];
+        find_build_dirs(&path, &mut dirs);
+        dirs
+    } else {
+        vec![config::build_dir(&path)]
+    };
+
+    let mut removed = 0;
+    for dir in build_dirs.iter().filter(|d| d.exists()) {
+        if fs::remove_dir_all(dir).is_err() {
+            return Err(format!("failed to remove {}", dir.display()).into());
+        }
+
+        println!("{} {}", "removed".bright_green(), dir.display());
+        removed += 1;
+    }
+
+    if removed == 0 {
+        println!("nothing to clean");
+    }
+
+    Ok(())
+}
+
+/// Finds the build directories of all problems in `dir` and its
+/// subdirectories, wherever they are kept.
+fn find_build_dirs(dir: &Path, dirs: &mut Vec<PathBuf>) {
+    let build_dir = config::build_dir(dir);
+    if build_dir.exists() && !dirs.contains(&build_dir) {
+        dirs.push(build_dir);
+    }
+
+    let entries = match fs::read_dir(dir) {
+        Ok(e) => e,
+        Err(_) => return,
+    };
+
+    for entry in entries.flatten() {
+        // Symbolic links are not followed, so that nothing outside `dir` is
+        // removed and loops are not a problem.
+        let is_dir = entry.file_type().is_ok_and(|t| t.is_dir());
+        if is_dir && entry.file_name() != STATE_DIR_NAME {
+            find_build_dirs(&entry.path(), dirs);
+        }
+    }
+}
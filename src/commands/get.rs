@@ -1,14 +1,18 @@
 use crate::config::Config;
 use crate::lang::Language;
+use crate::metadata::Metadata;
 use crate::problem::Problem;
 use crate::StdErr;
 use crate::CFG as cfg;
 use clap::ArgMatches;
 use colored::Colorize;
+use regex::Regex;
+use scraper::Html;
 use std::env;
 use std::fs;
-use std::io::{self, Read, Write};
+use std::io::{self, Write};
 use std::path::Path;
+use std::time::Duration;
 use zip::ZipArchive;
 
 pub async fn get(cmd: &ArgMatches<'_>) -> Result<(), StdErr> {
@@ -48,6 +52,11 @@ pub async fn get_and_create_problem(
         }
     }
 
+    let p_html = match p_res.text().await {
+        Ok(h) => h,
+        Err(_) => return Err("failed to read problem page from kattis".into()),
+    };
+
     let cwd = env::current_dir()?;
     let p_dir = cwd.join(id);
 
@@ -61,6 +70,7 @@ pub async fn get_and_create_problem(
     }
 
     fetch_tests(&p_dir, &p_url).await?;
+    parse_metadata(&p_html).save(&p_dir)?;
 
     let lang = if let Some(l) = lang_arg {
         match cfg.lang_from_file_ext(l) {
@@ -85,10 +95,78 @@ pub fn create_problem_url(id: &str) -> Result<String, StdErr> {
     Ok(format!("https://{}/problems/{}", host_name, id))
 }
 
+/// Fetches the problem page and stores the limits found on it in the problem's
+/// metadata file.
+pub async fn fetch_metadata(parent_dir: &Path, problem_url: &str) -> Result<(), StdErr> {
+    let res = reqwest::get(problem_url).await?;
+
+    let status = res.status();
+    if !status.is_success() {
+        return Err(format!("failed to fetch problem page (http status code {})", status).into());
+    }
+
+    let html = match res.text().await {
+        Ok(h) => h,
+        Err(_) => return Err("failed to read problem page from kattis".into()),
+    };
+
+    parse_metadata(&html).save(parent_dir)
+}
+
+fn parse_metadata(html: &str) -> Metadata {
+    let doc = Html::parse_document(html);
+    let text = doc.root_element().text().collect::<String>();
+
+    let time_limit_re = Regex::new(r"(?i)CPU Time limit:?\s*(\d+(?:\.\d+)?)\s*second").unwrap();
+    let time_limit = time_limit_re
+        .captures(&text)
+        .and_then(|c| c[1].parse::<f64>().ok())
+        .map(Duration::from_secs_f64);
+
+    let memory_limit_re = Regex::new(r"(?i)Memory limit:?\s*(\d+)\s*MB").unwrap();
+    let memory_limit = memory_limit_re
+        .captures(&text)
+        .and_then(|c| c[1].parse::<u64>().ok());
+
+    Metadata {
+        time_limit,
+        memory_limit,
+        validator_flags: parse_float_flags(&text),
+        ..Default::default()
+    }
+}
+
+/// Looks for a sentence in the problem statement such as "answers with an
+/// absolute or relative error of at most 10^{-6} are accepted" and turns it
+/// into the matching validator flags.
+fn parse_float_flags(text: &str) -> Vec<String> {
+    let tolerance_re = Regex::new(
+        r"(?i)(absolute|relative)( or relative| or absolute|/relative|/absolute)? error[^.]*?10\s*\^\s*\{?\s*[-\x{2212}]\s*(\d+)",
+    )
+    .unwrap();
+
+    let caps = match tolerance_re.captures(text) {
+        Some(c) => c,
+        None => return Vec::new(),
+    };
+
+    let flag = if caps.get(2).is_some() {
+        "float_tolerance"
+    } else if caps[1].to_lowercase() == "absolute" {
+        "float_absolute_tolerance"
+    } else {
+        "float_relative_tolerance"
+    };
+
+    vec![flag.to_string(), format!("1e-{}", &caps[3])]
+}
+
 pub async fn fetch_tests(parent_dir: &Path, problem_url: &str) -> Result<(), StdErr> {
     let t_dir = parent_dir.join("test");
     let t_dir = t_dir.as_path();
-    if fs::create_dir(t_dir).is_err() {
+    // The test directory already exists if custom tests were added before the
+    // samples were fetched.
+    if fs::create_dir_all(t_dir).is_err() {
         return Err("failed to create test directory at this location".into());
     }
 
@@ -124,22 +202,24 @@ pub async fn fetch_tests(parent_dir: &Path, problem_url: &str) -> Result<(), Std
         };
 
         if file.is_dir() {
-            return Err("unexpected directory in test samples".into());
+            continue;
         }
 
-        let name = file.name().to_owned();
+        // Samples are stored flat in the test directory. Besides the .in and
+        // .ans files, this keeps files such as the .interaction files of
+        // interactive problems.
+        let name = match Path::new(file.name()).file_name() {
+            Some(n) => n.to_owned(),
+            None => continue,
+        };
         let file_path = t_dir.join(&name);
+        let name = name.to_string_lossy();
         let mut dest = match fs::File::create(&file_path) {
             Ok(f) => f,
             Err(_) => return Err(format!("failed to create sample file {}", &name).into()),
         };
 
-        let mut content = String::new();
-        if file.read_to_string(&mut content).is_err() {
-            return Err("failed to read sample file from zip".into());
-        }
-
-        if dest.write_all(content.as_bytes()).is_err() {
+        if io::copy(&mut file, &mut dest).is_err() {
             return Err(format!("failed to write to file {}", &name).into());
         }
     }
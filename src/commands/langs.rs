@@ -4,16 +4,38 @@ use clap::ArgMatches;
 use colored::Colorize;
 
 pub async fn langs(_cmd: &ArgMatches<'_>) -> Result<(), StdErr> {
-    let mut langs: Vec<(String, &str)> = cfg
+    let mut langs: Vec<(String, &str, String)> = cfg
         .languages()
-        .map(|l| (l.to_string(), l.file_ext()))
+        .map(|l| {
+            // The default profile is marked with an asterisk.
+            let profiles = l
+                .profiles()
+                .iter()
+                .map(|p| {
+                    if l.default_profile() == Some(p.name.as_str()) {
+                        format!("{}*", p.name)
+                    } else {
+                        p.name.clone()
+                    }
+                })
+                .collect::<Vec<_>>()
+                .join(", ");
+
+            (l.to_string(), l.file_ext(), profiles)
+        })
         .collect();
 
     langs.sort();
 
-    println!("{:9}  {}", "Name".bright_cyan(), "Extension".bright_cyan());
-    for (name, ext) in langs {
-        println!("{:9}  {}", name, ext);
+    println!(
+        "{:9}  {:9}  {}",
+        "Name".bright_cyan(),
+        "Extension".bright_cyan(),
+        "Profiles".bright_cyan()
+    );
+    for (name, ext, profiles) in langs {
+        let line = format!("{:9}  {:9}  {}", name, ext, profiles);
+        println!("{}", line.trim_end());
     }
 
     Ok(())
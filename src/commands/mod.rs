@@ -1,3 +1,4 @@
+mod clean;
 mod config;
 mod get;
 mod history;
@@ -6,8 +7,10 @@ mod open;
 mod random;
 mod submit;
 mod test;
+mod tests;
 mod update;
 
+pub use clean::clean;
 pub use config::config;
 pub use get::get;
 pub use history::history;
@@ -16,4 +19,5 @@ pub use open::open;
 pub use random::random;
 pub use submit::submit;
 pub use test::test;
+pub use tests::tests;
 pub use update::update;
@@ -1,4 +1,5 @@
 use crate::config::Credentials;
+use crate::exit_code::{self, Failure};
 use crate::kattis_client::KattisClient;
 use crate::problem::Problem;
 use crate::StdErr;
@@ -177,7 +178,10 @@ async fn show_submission_status(
             print!("\r");
             io::stdout().flush().expect("failed to flush stdout");
 
-            return Err("kattis could not compile your code".into());
+            return Err(Failure::with_message(
+                exit_code::COMPILE_ERROR,
+                "kattis could not compile your code",
+            ));
         }
 
         if status.contains("new") || status.contains("compiling") {
@@ -258,7 +262,8 @@ async fn show_submission_status(
         thread::sleep(SLEEP_DURATION);
     }
 
-    let result_str = if fail.is_some() {
+    let rejected = fail.is_some();
+    let result_str = if rejected {
         "failed".bright_red()
     } else {
         "ok".bright_green()
@@ -276,5 +281,9 @@ async fn show_submission_status(
         result_str, num_passed, num_failed, runtime_str, suffix
     );
 
+    if rejected {
+        return Err(Failure::silent(exit_code::REJECTED));
+    }
+
     Ok(())
 }
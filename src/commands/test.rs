@@ -1,44 +1,354 @@
 use crate::commands::get;
-use crate::problem::Problem;
-use crate::utils::prompt_bool;
+use crate::compare::{self, CompareMode, Comparison, FloatTolerance};
+use crate::diagnostics::{self, Severity};
+use crate::diff;
+use crate::exit_code::{self, Failure};
+use crate::fingerprint::Fingerprint;
+use crate::interactive;
+use crate::lang::Language;
+use crate::metadata::{Metadata, METADATA_FILE_NAME};
+use crate::problem::{Problem, TestCase, CUSTOM_TEST_DIR_NAME, STATE_DIR_NAME, TEST_DIR_NAME};
+use crate::process::{self, Isolation, ResourceLimits, Usage, UNLIMITED};
+use crate::report::{self, CompileRecord, Report, ReportFormat, TestRecord};
+use crate::test_layout::TestLayout;
+use crate::utils::{self, prompt_bool};
+use crate::validator::Validator;
+use crate::verdict::{self, Verdict};
+use crate::watch;
 use crate::StdErr;
+use crate::CFG as cfg;
 use clap::ArgMatches;
 use colored::Colorize;
-use notify::{watcher, DebouncedEvent, RecursiveMode, Watcher};
+use std::collections::BTreeMap;
 use std::fs::{self, File};
-use std::io::{Read, Write};
-use std::path::PathBuf;
-use std::process::{Command, Stdio};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::mpsc::channel;
-use std::time;
+use std::thread;
 use std::time::Duration;
+use tempfile::TempDir;
 
 const CHECKBOX: &str = "\u{2705}"; // Green checkbox emoji
 const CROSSMARK: &str = "\u{274C}"; // Red X emoji
+const QUESTION_MARK: &str = "\u{2754}"; // White question mark emoji
+
+/// The time limit used when none is given and the problem's limit is unknown.
+const DEFAULT_TIME_LIMIT: Duration = Duration::from_secs(10);
+
+/// How many times the time limit a test case may run in wall-clock time before
+/// it is stopped, where its CPU time decides whether it exceeded the limit.
+const WALL_TIME_FACTOR: u32 = 3;
+
+/// The time limit used with `--bless` when none is given. The trusted solution
+/// is often a slow brute force, so it is only stopped if it seems to be stuck.
+const BLESS_TIME_LIMIT: Duration = Duration::from_secs(60 * 60);
+
+const MEGABYTE: u64 = 1024 * 1024;
+
+/// The output limit used by Kattis unless a problem specifies otherwise.
+const DEFAULT_OUTPUT_LIMIT: u64 = 8 * MEGABYTE;
+
+/// Messages that common runtimes print to stderr when an allocation fails.
+const OUT_OF_MEMORY_MESSAGES: &[&str] = &[
+    "std::bad_alloc",
+    "MemoryError",
+    "java.lang.OutOfMemoryError",
+    "out of memory",
+    "Cannot allocate memory",
+    "memory allocation of",
+];
+
+struct TestOptions {
+    time_limit: Duration,
+    compile_timeout: Duration,
+    limits: ResourceLimits,
+    comparison: Comparison,
+    validator: Option<Validator>,
+    interactor: Option<Validator>,
+    save_transcripts: bool,
+    /// Where the output of test cases without an answer is saved rather than
+    /// printed, if it is.
+    output_dir: Option<PathBuf>,
+    side_by_side: bool,
+    show_time: bool,
+    /// Whether the warnings of a successful compilation are shown.
+    show_warnings: bool,
+    /// Whether compiler diagnostics are printed in the format of GCC, for
+    /// editors to read, rather than with their source lines.
+    quickfix: bool,
+    fail_fast: bool,
+    /// Whether the working directory of a failed test case is kept rather
+    /// than removed.
+    keep_scratch: bool,
+    /// Whether the program runs in a sandbox without network access, where
+    /// only its working directory is writable.
+    sandbox: bool,
+    /// Whether the program is compiled even if nothing changed since it was
+    /// last compiled.
+    rebuild: bool,
+    jobs: usize,
+    report: Option<ReportFormat>,
+    report_file: Option<PathBuf>,
+    /// Whether the usual output is left out because the report is written to
+    /// stdout instead.
+    quiet: bool,
+}
+
+impl TestOptions {
+    fn from_args(
+        cmd: &ArgMatches<'_>,
+        lang: &Language,
+        metadata: &Metadata,
+        problem_dir: &Path,
+    ) -> Result<Self, StdErr> {
+        // The limits of the problem do not apply to a trusted solution that
+        // generates answers, since it only has to finish, not be fast. Limits
+        // given on the command line still do.
+        let bless = cmd.is_present("bless");
+        let problem_limit = |limit: Option<u64>| limit.filter(|_| !bless);
+
+        let time_limit = match parse_seconds(cmd, "time-limit")? {
+            Some(t) => t,
+            None if bless => BLESS_TIME_LIMIT,
+            None => metadata.time_limit.unwrap_or(DEFAULT_TIME_LIMIT),
+        };
+
+        // We can unwrap because the argument has a default value.
+        let compile_timeout = parse_seconds(cmd, "compile-timeout")?.unwrap();
+
+        // Like on Kattis, the stack may use all available memory by default.
+        // The profile may replace the memory limit of the problem.
+        let limits = ResourceLimits {
+            memory: parse_limit(cmd, "memory-limit", MEGABYTE)?
+                .or_else(|| lang.memory_limit())
+                .or_else(|| problem_limit(metadata.memory_limit).map(|m| m * MEGABYTE)),
+            stack: parse_limit(cmd, "stack-limit", MEGABYTE)?
+                .or_else(|| problem_limit(metadata.stack_limit).map(|m| m * MEGABYTE))
+                .or(Some(UNLIMITED)),
+            processes: parse_limit(cmd, "process-limit", 1)?
+                .or(problem_limit(metadata.process_limit)),
+            file_size: parse_limit(cmd, "file-size-limit", MEGABYTE)?
+                .or_else(|| problem_limit(metadata.file_size_limit).map(|m| m * MEGABYTE)),
+            output: parse_limit(cmd, "output-limit", MEGABYTE)?
+                .or_else(|| problem_limit(metadata.output_limit).map(|m| m * MEGABYTE))
+                .or(problem_limit(Some(DEFAULT_OUTPUT_LIMIT))),
+            // A program that keeps using the CPU is stopped soon after it
+            // exceeds the time limit instead of when its wall-clock time is up.
+            cpu_time: Some(time_limit.as_secs_f64().ceil() as u64 + 1),
+        };
+
+        if cmd.is_present("sandbox") && !cfg!(target_os = "linux") {
+            return Err("the sandbox is only supported on Linux".into());
+        }
+
+        let mut tolerance = FloatTolerance::from_validator_flags(&metadata.validator_flags)?;
+        if let Some(t) = parse_float_arg(cmd, "float-tolerance")? {
+            tolerance.absolute = Some(t);
+            tolerance.relative = Some(t);
+        }
+        if let Some(t) = parse_float_arg(cmd, "float-abs")? {
+            tolerance.absolute = Some(t);
+        }
+        if let Some(t) = parse_float_arg(cmd, "float-rel")? {
+            tolerance.relative = Some(t);
+        }
+
+        // We can unwrap because clap only allows the listed compare modes.
+        let mode = match cmd.value_of("compare") {
+            Some(m) => CompareMode::from_name(m).unwrap(),
+            None => cfg.default_compare_mode(),
+        };
+
+        let validator = match cmd.value_of("validator").or(metadata.validator.as_deref()) {
+            Some(v) => Some(Validator::new(
+                v,
+                problem_dir,
+                metadata.validator_flags.clone(),
+            )?),
+            None => None,
+        };
+
+        let interactor = match cmd
+            .value_of("interactor")
+            .or(metadata.interactor.as_deref())
+        {
+            Some(i) => Some(Validator::new(
+                i,
+                problem_dir,
+                metadata.validator_flags.clone(),
+            )?),
+            None => None,
+        };
+
+        // The interactor also decides the verdict, so a validator given on the
+        // command line would silently go unused.
+        if interactor.is_some() && cmd.is_present("validator") {
+            return Err("--validator cannot be used on interactive problems".into());
+        }
+
+        if interactor.is_none() && cmd.is_present("transcript") {
+            return Err("--transcript can only be used on interactive problems".into());
+        }
+
+        Ok(Self {
+            time_limit,
+            compile_timeout,
+            limits,
+            comparison: Comparison { mode, tolerance },
+            validator,
+            interactor,
+            save_transcripts: cmd.is_present("transcript"),
+            output_dir: if cmd.is_present("save-output") {
+                Some(problem_dir.join(STATE_DIR_NAME).join(OUTPUT_DIR_NAME))
+            } else {
+                None
+            },
+            side_by_side: cmd.is_present("side-by-side"),
+            show_time: cmd.is_present("time"),
+            show_warnings: cmd.is_present("warnings"),
+            quickfix: cmd.is_present("quickfix"),
+            fail_fast: cmd.is_present("fail-fast"),
+            keep_scratch: cmd.is_present("keep-scratch"),
+            sandbox: cmd.is_present("sandbox"),
+            rebuild: cmd.is_present("rebuild"),
+            jobs: parse_jobs(cmd)?,
+            // We can unwrap because clap only allows the listed formats.
+            report: cmd
+                .value_of("report")
+                .map(|r| ReportFormat::from_name(r).unwrap()),
+            report_file: cmd.value_of("report-file").map(PathBuf::from),
+            quiet: cmd.is_present("report") && !cmd.is_present("report-file"),
+        })
+    }
+}
+
+fn parse_seconds(cmd: &ArgMatches<'_>, arg: &str) -> Result<Option<Duration>, StdErr> {
+    let value = match cmd.value_of(arg) {
+        Some(v) => v,
+        None => return Ok(None),
+    };
+
+    match value.parse::<f64>() {
+        Ok(s) if s.is_finite() && s > 0.0 => Ok(Some(Duration::from_secs_f64(s))),
+        _ => Err(format!("please provide --{} as a positive number of seconds", arg).into()),
+    }
+}
+
+fn parse_float_arg(cmd: &ArgMatches<'_>, arg: &str) -> Result<Option<f64>, StdErr> {
+    let value = match cmd.value_of(arg) {
+        Some(v) => v,
+        None => return Ok(None),
+    };
+
+    match value.parse::<f64>() {
+        Ok(f) if f.is_finite() && f >= 0.0 => Ok(Some(f)),
+        _ => Err(format!("please provide --{} as a non-negative number", arg).into()),
+    }
+}
+
+fn parse_jobs(cmd: &ArgMatches<'_>) -> Result<usize, StdErr> {
+    if cmd.is_present("serial") {
+        return Ok(1);
+    }
+
+    // We can unwrap because the argument has a default value.
+    match cmd.value_of("jobs").unwrap().parse::<usize>() {
+        Ok(0) => Ok(thread::available_parallelism().map_or(1, |n| n.get())),
+        Ok(n) => Ok(n),
+        Err(_) => Err("please provide --jobs as a non-negative integer".into()),
+    }
+}
+
+/// Parses a limit argument given as a whole number of units or as "unlimited".
+/// The result is the number of units multiplied by `unit_size`.
+fn parse_limit(cmd: &ArgMatches<'_>, arg: &str, unit_size: u64) -> Result<Option<u64>, StdErr> {
+    let value = match cmd.value_of(arg) {
+        Some(v) => v,
+        None => return Ok(None),
+    };
+
+    if value.to_lowercase() == "unlimited" {
+        return Ok(Some(UNLIMITED));
+    }
+
+    match value
+        .parse::<u64>()
+        .ok()
+        .and_then(|v| v.checked_mul(unit_size))
+    {
+        Some(v) => Ok(Some(v)),
+        None => Err(format!(
+            "please provide --{} as a non-negative integer or \"unlimited\"",
+            arg
+        )
+        .into()),
+    }
+}
 
 pub async fn test(cmd: &ArgMatches<'_>) -> Result<(), StdErr> {
     let problem = Problem::from_args(cmd)?;
-    let lang = problem.lang();
+    let lang = problem.lang().with_profile(cmd.value_of("profile"))?;
     let file = problem.file();
 
-    let test_dir = problem.path().join("test");
-    if !test_dir.exists()
+    // Tests are only fetched if none of the folders they are read from exist.
+    let problem_dir = problem.path();
+    let layout = TestLayout::for_problem(&problem_dir)?;
+    let custom_dir = problem_dir.join(TEST_DIR_NAME).join(CUSTOM_TEST_DIR_NAME);
+    let has_tests = custom_dir.is_dir() || layout.dirs.iter().any(|d| problem_dir.join(d).is_dir());
+    if !has_tests
         && (cmd.is_present("fetch")
             || prompt_bool("no test cases found. do you want to fetch them from kattis?"))
     {
         fetch_tests(&problem).await?;
     }
 
+    // A report written inside the problem directory would otherwise start the
+    // next run in watch mode, and make the program compile again. So would the
+    // compiled program, for compile commands that write it next to the source.
+    let ignored = cmd
+        .value_of("report-file")
+        .map(PathBuf::from)
+        .into_iter()
+        .chain(lang.compiles_to_executable().then(|| lang.exe_path(&file)))
+        .collect::<Vec<_>>();
+
     let test_runner = || -> Result<(), StdErr> {
         let compile_cmd = lang.get_compile_cmd(&file)?;
+        let fingerprint = match &compile_cmd {
+            Some(c) => Some(Fingerprint::new(&file, &lang, c, &ignored)?),
+            None => None,
+        };
         let run_cmd = lang.get_run_cmd(&file)?;
-        let tests = problem.get_test_files()?;
+        let tests = select_tests(problem.get_test_files()?, cmd, &problem)?;
+        let options = TestOptions::from_args(cmd, &lang, &problem.metadata()?, &problem.path())?;
+
+        if cmd.is_present("bless") {
+            let layout = TestLayout::for_problem(&problem.path())?;
+            return bless_tests(
+                compile_cmd,
+                fingerprint.as_ref(),
+                &run_cmd,
+                &tests,
+                &options,
+                layout.answer_extension(),
+                cmd.is_present("yes"),
+            );
+        }
 
-        run_tests(compile_cmd, &run_cmd, &tests, cmd)
+        run_tests(
+            &problem,
+            compile_cmd,
+            fingerprint.as_ref(),
+            &run_cmd,
+            &tests,
+            &options,
+        )
     };
 
     if cmd.is_present("watch") {
-        watch(&problem, &test_runner)?;
+        watch::watch(&problem.path(), &ignored, test_runner)?;
     } else {
         test_runner()?;
     }
@@ -46,203 +356,1035 @@ pub async fn test(cmd: &ArgMatches<'_>) -> Result<(), StdErr> {
     Ok(())
 }
 
+/// Keeps the tests that match a name or glob pattern given on the command line,
+/// or all of them if none are given. With `--failed`, only the tests that
+/// failed in the previous run are kept. With `--watch`, those tests are run
+/// first.
+fn select_tests(
+    mut tests: Vec<TestCase>,
+    cmd: &ArgMatches<'_>,
+    problem: &Problem,
+) -> Result<Vec<TestCase>, StdErr> {
+    if let Some(patterns) = cmd.values_of("TESTS") {
+        let patterns = patterns.collect::<Vec<_>>();
+
+        for pattern in &patterns {
+            if !tests.iter().any(|t| utils::glob_matches(pattern, &t.name)) {
+                return Err(format!("no test matches \"{}\"", pattern).into());
+            }
+        }
+
+        tests.retain(|t| patterns.iter().any(|p| utils::glob_matches(p, &t.name)));
+    }
+
+    // With no failures to rerun, everything is run rather than nothing, which
+    // would look like a success.
+    if cmd.is_present("failed") {
+        let failed = load_failed_tests(&problem.state_dir());
+        if tests.iter().any(|t| failed.contains(&t.name)) {
+            tests.retain(|t| failed.contains(&t.name));
+        } else if !cmd.is_present("report") || cmd.is_present("report-file") {
+            println!("no failed tests recorded, so all tests are run");
+        }
+    }
+
+    if cmd.is_present("watch") {
+        let failed = load_failed_tests(&problem.state_dir());
+        tests.sort_by_key(|t| !failed.contains(&t.name));
+    }
+
+    Ok(tests)
+}
+
+/// The file in the state directory listing the tests that failed when they
+/// were last run, one name per line.
+const FAILED_TESTS_FILE_NAME: &str = "failed-tests";
+
+fn load_failed_tests(state_dir: &Path) -> Vec<String> {
+    match fs::read_to_string(state_dir.join(FAILED_TESTS_FILE_NAME)) {
+        Ok(text) => text.lines().map(String::from).collect(),
+        Err(_) => Vec::new(),
+    }
+}
+
+/// Updates the list of failed tests with the outcome of the tests that were
+/// just run. Tests that were not run keep their previous outcome.
+fn save_failed_tests(state_dir: &Path, run: &[String], failed: &[String]) -> Result<(), StdErr> {
+    let mut names = load_failed_tests(state_dir);
+    names.retain(|name| !run.contains(name));
+    names.extend(failed.iter().cloned());
+    names.sort_by(|a, b| utils::natural_cmp(a, b));
+
+    let mut text = names.join("\n");
+    text.push('\n');
+
+    let path = state_dir.join(FAILED_TESTS_FILE_NAME);
+    if fs::create_dir_all(state_dir)
+        .and_then(|_| fs::write(&path, text))
+        .is_err()
+    {
+        return Err(format!("failed to write {}", path.display()).into());
+    }
+
+    Ok(())
+}
+
 async fn fetch_tests(problem: &Problem<'_>) -> Result<(), StdErr> {
     let problem_url = get::create_problem_url(&problem.name())?;
-    get::fetch_tests(&problem.path(), &problem_url).await
+    get::fetch_tests(&problem.path(), &problem_url).await?;
+
+    if !problem.path().join(METADATA_FILE_NAME).exists() {
+        get::fetch_metadata(&problem.path(), &problem_url).await?;
+    }
+
+    Ok(())
 }
 
 fn run_tests(
+    problem: &Problem,
     compile_cmd: Option<Vec<String>>,
+    fingerprint: Option<&Fingerprint>,
     run_cmd: &[String],
-    tests: &[(PathBuf, PathBuf)],
-    cmd: &ArgMatches<'_>,
+    tests: &[TestCase],
+    options: &TestOptions,
 ) -> Result<(), StdErr> {
-    if let Some(cmd) = compile_cmd {
-        let mut compile_parts = cmd.iter();
-        let compile_prog = compile_parts
-            .next()
-            .ok_or_else::<StdErr, _>(|| "compile command was empty".into())?;
-        let compile_args: Vec<_> = compile_parts.collect();
-
-        let mut command = Command::new(compile_prog);
-        command.args(&compile_args).stderr(Stdio::piped());
-
-        let child = match command.spawn() {
-            Ok(c) => c,
-            Err(_) => return Err(format!("failed to execute command \"{}\"", compile_prog).into()),
+    let mut report = Report {
+        problem: problem.name(),
+        ..Default::default()
+    };
+
+    let compile_steps = [
+        ("program", compile_cmd.as_deref(), fingerprint),
+        (
+            "validator",
+            options.validator.as_ref().and_then(Validator::compile_cmd),
+            None,
+        ),
+        (
+            "interactor",
+            options.interactor.as_ref().and_then(Validator::compile_cmd),
+            None,
+        ),
+    ];
+
+    for &(what, cmd, fingerprint) in compile_steps.iter() {
+        let cmd = match cmd {
+            Some(c) => c,
+            None => continue,
         };
 
-        let output = match child.wait_with_output() {
-            Ok(o) => o,
-            Err(_) => return Err("failed to wait for compilation output".into()),
+        let record = match compile_if_changed(cmd, fingerprint, options, what)? {
+            Some(r) => r,
+            None => continue,
         };
+        let failure = compile_failure(&record, options);
+        report.compilations.push(record);
 
-        if !output.status.success() {
-            let stderr = match String::from_utf8(output.stderr) {
-                Ok(s) => s,
-                Err(_) => return Err("compilation output stderr contained invalid UTF-8".into()),
-            };
+        if let Some(e) = failure {
+            write_report(&report, options)?;
+            return Err(e);
+        }
+    }
+
+    let mut verdict_counts = BTreeMap::new();
+    let mut unchecked = Vec::new();
+    let mut run = Vec::new();
+    let mut failed = Vec::new();
+    let mut max_usage: Option<Usage> = None;
+
+    // Gives whether to go on with the remaining tests.
+    let mut record_result = |result: TestResult| {
+        if !options.quiet {
+            print_test_result(&result, options);
+        }
+
+        let name = result.name.clone();
+        match result.verdict {
+            Some(verdict) => {
+                *verdict_counts.entry(verdict).or_insert(0) += 1;
+                if !verdict.is_passing() {
+                    failed.push(name.clone());
+                }
+            }
+            None => unchecked.push(name.clone()),
+        }
+        run.push(name);
+        report.tests.push(test_record(&result));
+
+        if let Some(usage) = result.output.usage {
+            let max = max_usage.get_or_insert_with(Usage::default);
+            max.cpu_time = max.cpu_time.max(usage.cpu_time);
+            max.peak_memory = max.peak_memory.max(usage.peak_memory);
+        }
+
+        !(options.fail_fast && result.verdict.is_some_and(|v| !v.is_passing()))
+    };
+
+    if !options.quiet {
+        println!("running {} tests", tests.len());
+    }
+
+    if options.jobs <= 1 {
+        for test in tests {
+            if !options.quiet {
+                print!("test {} ... ", test.name);
+                io::stdout().flush().expect("failed to flush stdout");
+            }
+
+            if !record_result(run_test(test, run_cmd, options)?) {
+                break;
+            }
+        }
+    } else {
+        run_tests_in_parallel(tests, run_cmd, options, |result| {
+            if !options.quiet {
+                print!("test {} ... ", result.name);
+            }
+            record_result(result)
+        })?;
+    }
+
+    let all_passed = verdict_counts.keys().all(|v| v.is_passing());
+    let test_result = if all_passed {
+        "ok".bright_green()
+    } else {
+        "failed".bright_red()
+    };
+    let counts = if verdict_counts.is_empty() && unchecked.is_empty() {
+        "no tests were run".to_string()
+    } else if verdict_counts.is_empty() {
+        "no tests were checked".to_string()
+    } else {
+        verdict_counts
+            .iter()
+            .map(|(verdict, count)| format!("{} {}", count, verdict))
+            .collect::<Vec<_>>()
+            .join("; ")
+    };
+
+    if !options.quiet {
+        println!("\ntest result: {}. {}.", test_result, counts);
+
+        if !unchecked.is_empty() {
+            println!("no answer to compare: {}.", unchecked.join(", "));
+        }
+
+        if run.len() < tests.len() {
+            println!(
+                "stopped after the first failure. {} tests were not run.",
+                tests.len() - run.len()
+            );
+        }
 
-            println!("{}:\n{}\n", "compilation error".bright_red(), stderr.trim());
+        if let Some(usage) = max_usage {
+            print_max_usage(&usage, options);
+        }
+    }
+
+    write_report(&report, options)?;
+    save_failed_tests(&problem.state_dir(), &run, &failed)?;
+
+    if all_passed {
+        Ok(())
+    } else {
+        Err(Failure::silent(exit_code::TESTS_FAILED))
+    }
+}
+
+/// Writes the report in the requested format, if any, to the report file or
+/// to stdout.
+fn write_report(report: &Report, options: &TestOptions) -> Result<(), StdErr> {
+    let format = match options.report {
+        Some(f) => f,
+        None => return Ok(()),
+    };
 
-            return Err("program failed to compile".into());
+    let text = report.render(format);
+
+    match &options.report_file {
+        Some(path) => {
+            if fs::write(path, text).is_err() {
+                return Err(format!("failed to write report to {}", path.display()).into());
+            }
         }
+        None => print!("{}", text),
     }
 
-    let mut run_parts = run_cmd.iter();
-    let run_prog = run_parts
-        .next()
-        .ok_or_else::<StdErr, _>(|| "run command was empty".into())?;
-    let run_args: Vec<_> = run_parts.collect();
-    let mut fails = 0;
+    Ok(())
+}
 
-    println!("running {} tests", tests.len());
+fn test_record(result: &TestResult) -> TestRecord {
+    let output = &result.output;
 
-    for (test_in, test_ans) in tests {
-        // We can unwrap because the file extension check earlier would ensure
-        // that the file was skipped if it did not have a valid name
-        let test_label = test_in.file_stem().unwrap().to_str().unwrap().to_string();
+    TestRecord {
+        name: result.name.clone(),
+        verdict: result.verdict,
+        elapsed: output.elapsed,
+        cpu_time: output.usage.map(|u| u.cpu_time),
+        peak_memory: output.usage.map(|u| u.peak_memory),
+        exit_status: output.status.as_ref().map(verdict::exit_reason),
+        exit_code: output.status.and_then(|s| s.code()),
+        stdout: report::excerpt(&output.stdout),
+        stderr: report::excerpt(&output.stderr),
+        judge_message: result.judge_message.clone(),
+    }
+}
 
-        print!("test {} ... ", test_label);
+/// Runs a compile command, printing the compiler's errors if it fails, and its
+/// warnings if it succeeds and they are asked for. `what` names the program
+/// being compiled.
+fn compile(
+    compile_cmd: &[String],
+    options: &TestOptions,
+    what: &str,
+) -> Result<CompileRecord, StdErr> {
+    let output = process::run(
+        compile_cmd,
+        Stdio::null(),
+        options.compile_timeout,
+        &ResourceLimits::default(),
+        &Isolation::default(),
+    )?;
 
-        let mut f_in = File::open(test_in)?;
-        let mut in_buf = Vec::new();
-        f_in.read_to_end(&mut in_buf)?;
+    let success = output.status.is_some_and(|s| s.success());
+    if output.status.is_some() && !options.quiet {
+        print_compiler_messages(&String::from_utf8_lossy(&output.stderr), success, options);
+    }
 
-        let ans = fs::read_to_string(test_ans)?;
+    Ok(CompileRecord {
+        target: what.to_string(),
+        success,
+        timed_out: output.timed_out(),
+        elapsed: output.elapsed,
+        output: report::excerpt(&output.stderr),
+    })
+}
 
-        let start_time = time::Instant::now();
-        let mut command = Command::new(run_prog);
-        command
-            .args(&run_args)
-            .stdin(Stdio::piped())
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped());
+/// Compiles a program unless its fingerprint shows that it was already
+/// compiled from the same source, in which case `None` is given. The
+/// fingerprint is saved when the compilation succeeds.
+fn compile_if_changed(
+    compile_cmd: &[String],
+    fingerprint: Option<&Fingerprint>,
+    options: &TestOptions,
+    what: &str,
+) -> Result<Option<CompileRecord>, StdErr> {
+    if !options.rebuild && fingerprint.is_some_and(Fingerprint::is_up_to_date) {
+        if !options.quiet {
+            println!(
+                "{} {} since nothing changed",
+                "skipped compiling".bright_cyan(),
+                what
+            );
+        }
+        return Ok(None);
+    }
 
-        let mut child = match command.spawn() {
-            Ok(c) => c,
-            Err(_) => return Err(format!("failed to execute command \"{}\"", run_prog).into()),
+    let record = compile(compile_cmd, options, what)?;
+    if record.success {
+        if let Some(f) = fingerprint {
+            f.save()?;
+        }
+    }
+
+    Ok(Some(record))
+}
+
+fn print_compiler_messages(messages: &str, success: bool, options: &TestOptions) {
+    let diagnostics = diagnostics::parse(messages);
+
+    if success {
+        let warnings = diagnostics
+            .into_iter()
+            .filter(|d| d.severity != Severity::Error)
+            .collect::<Vec<_>>();
+
+        if options.show_warnings && !warnings.is_empty() {
+            if !options.quickfix {
+                println!("{}:\n", "compiler warnings".bright_yellow());
+            }
+            diagnostics::print_all(&warnings, options.quickfix);
+        }
+        return;
+    }
+
+    // The output is shown as it is if it is in a format that is not
+    // understood, or if the cause of the failure was not among what was, as
+    // with errors from the linker.
+    if !diagnostics.iter().any(|d| d.severity == Severity::Error) {
+        println!(
+            "{}:\n{}\n",
+            "compilation error".bright_red(),
+            messages.trim()
+        );
+        return;
+    }
+
+    if !options.quickfix {
+        println!("{}:\n", "compilation error".bright_red());
+    }
+    diagnostics::print_all(&diagnostics, options.quickfix);
+}
+
+/// The error to stop with if a compilation failed.
+fn compile_failure(record: &CompileRecord, options: &TestOptions) -> Option<StdErr> {
+    let message = if record.timed_out {
+        format!(
+            "compilation of {} timed out after {:.2}s",
+            record.target,
+            options.compile_timeout.as_secs_f64()
+        )
+    } else if !record.success {
+        format!("{} failed to compile", record.target)
+    } else {
+        return None;
+    };
+
+    Some(Failure::with_message(exit_code::COMPILE_ERROR, message))
+}
+
+/// Runs a trusted solution on the tests and saves its output as their
+/// answers. New answer files get `answer_extension`. The answers of official
+/// samples are only overwritten after confirming, unless `skip_confirmation`
+/// is set.
+fn bless_tests(
+    compile_cmd: Option<Vec<String>>,
+    fingerprint: Option<&Fingerprint>,
+    run_cmd: &[String],
+    tests: &[TestCase],
+    options: &TestOptions,
+    answer_extension: &str,
+    skip_confirmation: bool,
+) -> Result<(), StdErr> {
+    if options.interactor.is_some() {
+        return Err("the answers of interactive problems cannot be generated".into());
+    }
+
+    // The official samples from Kattis must never be replaced by accident.
+    let samples = tests
+        .iter()
+        .filter(|t| t.answer.is_some() && !t.custom)
+        .map(|t| t.name.as_str())
+        .collect::<Vec<_>>();
+    let overwrite = samples.is_empty()
+        || skip_confirmation
+        || prompt_bool(&format!(
+            "this overwrites the answers of the official samples {}. do you want to continue?",
+            samples.join(", ")
+        ));
+    let tests = tests
+        .iter()
+        .filter(|t| overwrite || t.answer.is_none() || t.custom)
+        .collect::<Vec<_>>();
+
+    if let Some(cmd) = &compile_cmd {
+        let record = compile_if_changed(cmd, fingerprint, options, "program")?;
+        if let Some(e) = record.and_then(|r| compile_failure(&r, options)) {
+            return Err(e);
+        }
+    }
+
+    println!("blessing {} tests", tests.len());
+
+    let mut failed = 0;
+    for test in tests {
+        print!("test {} ... ", test.name);
+        io::stdout().flush().expect("failed to flush stdout");
+
+        let scratch_dir = create_scratch_dir()?;
+        let output = process::run(
+            run_cmd,
+            Stdio::from(File::open(&test.input)?),
+            wall_time_limit(options),
+            &options.limits,
+            &isolation(&scratch_dir, options),
+        )?;
+
+        if let Some(verdict) = exit_verdict(&output, options) {
+            print!("{} {}", CROSSMARK, verdict.to_string().bright_red());
+            print_usage(&output, options);
+            println!();
+
+            if let Some(status) = &output.status {
+                println!("{}\n", verdict::exit_reason(status));
+                print_stream("Stderr:", &output.stderr);
+            }
+
+            failed += 1;
+            continue;
+        }
+
+        let path = match &test.answer {
+            Some(a) => a.clone(),
+            None => test.input.with_extension(answer_extension),
+        };
+        let change = match fs::read(&path) {
+            Ok(old) if old == output.stdout => "unchanged",
+            Ok(_) => "updated",
+            Err(_) => "created",
         };
 
+        if fs::write(&path, &output.stdout).is_err() {
+            return Err(format!("failed to write answer to {}", path.display()).into());
+        }
+
+        print!("{} {}", CHECKBOX, change);
+        print_usage(&output, options);
+        println!();
+    }
+
+    if failed > 0 {
+        println!(
+            "\nbless result: {}. {} tests failed to run.",
+            "failed".bright_red(),
+            failed
+        );
+        return Err(Failure::silent(exit_code::TESTS_FAILED));
+    }
+
+    println!("\nbless result: {}.", "ok".bright_green());
+
+    Ok(())
+}
+
+struct TestResult {
+    name: String,
+    input: PathBuf,
+    answer: Vec<u8>,
+    output: process::Output,
+    /// The verdict, or `None` if the program ran without problems but there
+    /// was no answer to check its output against.
+    verdict: Option<Verdict>,
+    /// An explanation of the verdict from the output validator.
+    judge_message: Option<String>,
+    /// The exchange between the program and the interactor on interactive
+    /// problems.
+    transcript: Option<String>,
+    /// The working directory of the program, if it was kept for inspection.
+    scratch_dir: Option<PathBuf>,
+}
+
+/// Creates the empty directory that a test case is run in, such that files the
+/// program reads or writes relative to its working directory behave like on
+/// the judge. It is removed when dropped.
+fn create_scratch_dir() -> Result<TempDir, StdErr> {
+    match tempfile::Builder::new().prefix("kitty-scratch-").tempdir() {
+        Ok(d) => Ok(d),
+        Err(_) => Err("failed to create a working directory for the test".into()),
+    }
+}
+
+/// How a test case is run in its scratch directory.
+fn isolation(scratch_dir: &TempDir, options: &TestOptions) -> Isolation {
+    Isolation {
+        working_dir: Some(scratch_dir.path().to_path_buf()),
+        sandbox: options.sandbox,
+    }
+}
+
+/// Keeps the working directory of a failed test case if asked to, giving its
+/// path. Otherwise it is removed.
+fn keep_scratch_dir(
+    scratch_dir: TempDir,
+    verdict: Option<Verdict>,
+    options: &TestOptions,
+) -> Option<PathBuf> {
+    if options.keep_scratch && verdict.is_some_and(|v| !v.is_passing()) {
+        Some(scratch_dir.keep())
+    } else {
+        None
+    }
+}
+
+fn run_test(
+    test: &TestCase,
+    run_cmd: &[String],
+    options: &TestOptions,
+) -> Result<TestResult, StdErr> {
+    let test_in = &test.input;
+
+    if let Some(interactor) = &options.interactor {
+        return run_interactive_test(test, run_cmd, interactor, options);
+    }
+
+    // The program reads the input file directly, so it is never held in memory
+    // by kitty.
+    let input = File::open(test_in)?;
+    let answer = match &test.answer {
+        Some(test_ans) => Some(fs::read(test_ans)?),
+        None => None,
+    };
+
+    let scratch_dir = create_scratch_dir()?;
+    let output = process::run(
+        run_cmd,
+        Stdio::from(input),
+        wall_time_limit(options),
+        &options.limits,
+        &isolation(&scratch_dir, options),
+    )?;
+
+    let (verdict, judge_message) = match (&test.answer, &answer) {
+        (Some(test_ans), Some(answer)) => {
+            let (verdict, judge_message) = judge(&output, test_in, test_ans, answer, options)?;
+            (Some(verdict), judge_message)
+        }
+        _ => (exit_verdict(&output, options), None),
+    };
+
+    if let (None, Some(dir)) = (verdict, &options.output_dir) {
+        let path = saved_output_path(dir, &test.name);
+        // We can unwrap because the path is always inside the output directory.
+        if fs::create_dir_all(path.parent().unwrap())
+            .and_then(|_| fs::write(&path, &output.stdout))
+            .is_err()
         {
-            let child_stdin = match child.stdin.as_mut() {
-                Some(c) => c,
-                None => return Err("failed to capture stdin of program".into()),
-            };
+            return Err(format!("failed to write output to {}", path.display()).into());
+        }
+    }
+
+    Ok(TestResult {
+        name: test.name.clone(),
+        input: test_in.to_path_buf(),
+        answer: answer.unwrap_or_default(),
+        output,
+        verdict,
+        judge_message,
+        transcript: None,
+        scratch_dir: keep_scratch_dir(scratch_dir, verdict, options),
+    })
+}
+
+/// How long a test case may run before it is stopped. Where the CPU time of the
+/// program is known, it decides whether the time limit was exceeded instead,
+/// so the program is given longer such that waiting for a turn on a busy
+/// machine, as when running tests in parallel, does not make it fail. A program
+/// that sleeps or waits for input forever is still stopped.
+fn wall_time_limit(options: &TestOptions) -> Duration {
+    if cfg!(unix) {
+        options.time_limit * WALL_TIME_FACTOR + Duration::from_secs(1)
+    } else {
+        options.time_limit
+    }
+}
+
+fn run_interactive_test(
+    test: &TestCase,
+    run_cmd: &[String],
+    interactor: &Validator,
+    options: &TestOptions,
+) -> Result<TestResult, StdErr> {
+    let test_in = &test.input;
+
+    // An interactive test cannot run without the interactor, so one without an
+    // answer is judged with an empty answer file instead.
+    let empty_answer;
+    let test_ans = match &test.answer {
+        Some(a) => a.as_path(),
+        None => {
+            empty_answer = tempfile::NamedTempFile::new()?;
+            empty_answer.path()
+        }
+    };
+
+    let scratch_dir = create_scratch_dir()?;
+    let interaction = interactive::run(
+        run_cmd,
+        interactor,
+        test_in,
+        test_ans,
+        wall_time_limit(options),
+        &options.limits,
+        &isolation(&scratch_dir, options),
+    )?;
+
+    if options.save_transcripts {
+        let path = test_in.with_extension("transcript");
+        if fs::write(&path, &interaction.transcript).is_err() {
+            return Err(format!("failed to write transcript to {}", path.display()).into());
+        }
+    }
+
+    let output = interaction.output;
+    let (verdict, judge_message) = match exit_verdict(&output, options) {
+        // A rejection by the interactor explains why the program crashed or
+        // was stopped better than the crash itself does.
+        Some(Verdict::RunTimeError) | Some(Verdict::MemoryLimitExceeded)
+            if !interaction.judgement.0.is_accepted() =>
+        {
+            interaction.judgement
+        }
+        Some(verdict) => (verdict, None),
+        None => interaction.judgement,
+    };
+
+    Ok(TestResult {
+        name: test.name.clone(),
+        input: test_in.to_path_buf(),
+        answer: Vec::new(),
+        output,
+        verdict: Some(verdict),
+        judge_message,
+        transcript: Some(interaction.transcript),
+        scratch_dir: keep_scratch_dir(scratch_dir, Some(verdict), options),
+    })
+}
+
+/// Runs the tests on a pool of `options.jobs` worker threads. Each test is
+/// timed by the worker running it, so waiting in line does not count towards
+/// its time. Results are passed to `on_result` in the same order as the tests,
+/// and no more tests are started once it returns `false`.
+fn run_tests_in_parallel<F: FnMut(TestResult) -> bool>(
+    tests: &[TestCase],
+    run_cmd: &[String],
+    options: &TestOptions,
+    mut on_result: F,
+) -> Result<(), StdErr> {
+    let next_test = AtomicUsize::new(0);
+    let stopped = AtomicBool::new(false);
+    let (tx, rx) = channel();
+
+    thread::scope(|scope| {
+        for _ in 0..options.jobs.min(tests.len()) {
+            let tx = tx.clone();
+            let next_test = &next_test;
+            let stopped = &stopped;
+
+            scope.spawn(move || loop {
+                if stopped.load(Ordering::SeqCst) {
+                    break;
+                }
+
+                let i = next_test.fetch_add(1, Ordering::SeqCst);
+                let test = match tests.get(i) {
+                    Some(t) => t,
+                    None => break,
+                };
+
+                // Errors cannot be sent between threads, so they are passed on
+                // as their messages.
+                let result = run_test(test, run_cmd, options).map_err(|e| e.to_string());
+
+                // Sending fails if the results are no longer wanted.
+                if tx.send((i, result)).is_err() {
+                    break;
+                }
+            });
+        }
+
+        // Without this, the loop below would wait forever since the channel
+        // is only closed once every sender is dropped.
+        drop(tx);
+
+        let mut finished = BTreeMap::new();
+        let mut next_to_report = 0;
 
-            match child_stdin.write_all(&in_buf) {
-                Ok(_) => {}
-                Err(_) => return Err("failed to write to stdin of program".into()),
+        for (i, result) in rx {
+            finished.insert(i, result);
+
+            while let Some(result) = finished.remove(&next_to_report) {
+                if !on_result(result?) {
+                    // The tests that are still running are left to finish,
+                    // but their results are thrown away.
+                    stopped.store(true, Ordering::SeqCst);
+                    return Ok(());
+                }
+                next_to_report += 1;
             }
         }
 
-        let output = match child.wait_with_output() {
-            Ok(o) => o,
-            Err(_) => return Err("failed to wait for program output".into()),
-        };
-        let elapsed_time = start_time.elapsed();
+        Ok(())
+    })
+}
+
+/// Decides the verdict of a single test case from the output of the program.
+/// If the output is checked by a validator, its explanation of the verdict is
+/// returned too.
+fn judge(
+    output: &process::Output,
+    test_in: &Path,
+    test_ans: &Path,
+    answer: &[u8],
+    options: &TestOptions,
+) -> Result<(Verdict, Option<String>), StdErr> {
+    if let Some(verdict) = exit_verdict(output, options) {
+        return Ok((verdict, None));
+    }
 
-        let stdout = match String::from_utf8(output.stdout) {
-            Ok(s) => s,
-            Err(_) => return Err("program output (stdout) contained invalid UTF-8".into()),
+    match &options.validator {
+        Some(validator) => validator.validate(test_in, test_ans, &output.stdout),
+        None => Ok((
+            compare::compare(answer, &output.stdout, options.comparison),
+            None,
+        )),
+    }
+}
+
+/// Decides the verdict from how the program exited, if it did not exit
+/// normally within its limits.
+fn exit_verdict(output: &process::Output, options: &TestOptions) -> Option<Verdict> {
+    let status = match output.status {
+        Some(s) => s,
+        None => return Some(Verdict::TimeLimitExceeded),
+    };
+
+    let cpu_time = output.usage.map_or(Duration::ZERO, |u| u.cpu_time);
+    if cpu_time > options.time_limit {
+        return Some(Verdict::TimeLimitExceeded);
+    }
+
+    if output.output_limit_exceeded {
+        return Some(Verdict::OutputLimitExceeded);
+    }
+
+    // The memory limit only stops allocations that go beyond it, so a program
+    // that uses more memory than allowed may still run to the end.
+    if let (Some(usage), Some(limit)) = (output.usage, options.limits.memory) {
+        if limit != UNLIMITED && usage.peak_memory > limit {
+            return Some(Verdict::MemoryLimitExceeded);
+        }
+    }
+
+    if !status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+
+        return if exceeded_memory_limit(&stderr, &options.limits) {
+            Some(Verdict::MemoryLimitExceeded)
+        } else {
+            Some(Verdict::RunTimeError)
         };
+    }
 
-        if output.status.success() {
-            let ans_str = reformat_ans_str(&ans);
-            let out_str = reformat_ans_str(&stdout);
-            let is_success = ans_str == out_str;
+    None
+}
 
-            print!("{}", if is_success { CHECKBOX } else { CROSSMARK });
+/// Guesses whether a failed program ran out of memory when its peak memory
+/// usage did not show it, for example because an allocation that would have
+/// gone beyond the limit failed. Programs are not told why an allocation
+/// failed, so this relies on the error message of the runtime.
+fn exceeded_memory_limit(stderr: &str, limits: &ResourceLimits) -> bool {
+    let is_limited = matches!(limits.memory, Some(m) if m != UNLIMITED);
 
-            if cmd.is_present("time") {
-                print!(" in {:.2}s", elapsed_time.as_secs_f64());
-            }
+    is_limited && OUT_OF_MEMORY_MESSAGES.iter().any(|m| stderr.contains(m))
+}
+
+fn print_test_result(result: &TestResult, options: &TestOptions) {
+    let TestResult {
+        answer,
+        output,
+        verdict,
+        judge_message,
+        transcript,
+        scratch_dir,
+        ..
+    } = result;
 
+    let verdict = match verdict {
+        Some(v) => v,
+        None => {
+            print!("{} {}", QUESTION_MARK, "no answer to compare".bright_cyan());
+            print_usage(output, options);
             println!();
+            print_unchecked_output(result, options);
+            return;
+        }
+    };
+
+    if verdict.is_accepted() {
+        print!("{}", CHECKBOX);
+    } else if verdict.is_passing() {
+        print!("{} {}", CHECKBOX, verdict.to_string().bright_yellow());
+    } else {
+        print!("{} {}", CROSSMARK, verdict.to_string().bright_red());
+    }
+
+    print_usage(output, options);
+    println!();
+
+    if let Some(dir) = scratch_dir {
+        println!("working directory kept at {}", dir.display());
+    }
+
+    if let Some(transcript) = transcript {
+        if !verdict.is_accepted() {
+            print_interaction(result, transcript);
+        }
 
-            if !is_success {
-                fails += 1;
+        return;
+    }
 
-                println!(
-                    "{}\n{}\n\n{}\n{}\n",
-                    "Expected:".underline(),
-                    ans_str,
-                    "Actual:".underline(),
-                    out_str
-                );
+    match verdict {
+        Verdict::WrongAnswer | Verdict::JudgeError if judge_message.is_some() => println!(
+            "{}\n{}\n\n{}\n{}\n",
+            "Judge message:".underline(),
+            judge_message.as_deref().unwrap_or_default(),
+            "Actual:".underline(),
+            reformat_ans_str(&output.stdout)
+        ),
+        Verdict::WrongAnswer | Verdict::PresentationWarning => {
+            if *verdict == Verdict::PresentationWarning {
+                println!("the output is only correct when ignoring whitespace");
             }
-        } else {
-            fails += 1;
 
-            let stderr = match String::from_utf8(output.stderr) {
-                Ok(s) => s,
-                Err(_) => return Err("program output (stderr) contained invalid UTF-8".into()),
+            diff::print_diff(
+                answer,
+                &output.stdout,
+                options.comparison,
+                options.side_by_side,
+            );
+            println!();
+        }
+        Verdict::OutputLimitExceeded => println!(
+            "the program wrote more than {} to stdout\n",
+            format_megabytes(output.stdout.len() as u64)
+        ),
+        Verdict::RunTimeError | Verdict::MemoryLimitExceeded => {
+            if let Some(status) = &output.status {
+                println!("{}\n", verdict::exit_reason(status));
+            }
+
+            print_stream("Output:", &output.stdout);
+            print_stream("Stderr:", &output.stderr);
+        }
+        Verdict::Accepted | Verdict::TimeLimitExceeded | Verdict::JudgeError => {}
+    }
+}
+
+/// Prints what a program wrote to stdout or stderr under the given label,
+/// unless it wrote nothing there.
+fn print_stream(label: &str, text: &[u8]) {
+    let text = String::from_utf8_lossy(text);
+    if !text.trim().is_empty() {
+        println!("{}\n{}\n", label.underline(), text.trim());
+    }
+}
+
+/// Prints how long the test case ran and how much memory it used on the line
+/// of its result.
+fn print_usage(output: &process::Output, options: &TestOptions) {
+    let wall_time = output.elapsed.as_secs_f64();
+    match (output.status, output.usage) {
+        (None, _) => print!(" (stopped after {:.2}s)", wall_time),
+        (Some(_), Some(usage)) => {
+            let wall_time = if options.show_time {
+                format!(" ({:.2}s wall)", wall_time)
+            } else {
+                String::new()
             };
 
-            println!(
-                "{}:\n{}\n{}\n",
-                "program error".bright_red(),
-                stdout.trim(),
-                stderr.trim()
+            let stats = format!(
+                "{:.2}s CPU{}, {}",
+                usage.cpu_time.as_secs_f64(),
+                wall_time,
+                format_megabytes(usage.peak_memory)
             );
+            print!(" {}", stats.dimmed());
         }
+        (Some(_), None) if options.show_time => print!(" in {:.2}s", wall_time),
+        _ => {}
     }
+}
 
-    let test_result = if fails == 0 {
-        "ok".bright_green()
-    } else {
-        "failed".bright_red()
-    };
-    let num_passed = tests.len() - fails;
+/// The number of lines shown from the output of a test case without an answer
+/// unless the output is saved to a file.
+const OUTPUT_PREVIEW_LINES: usize = 40;
+
+/// The directory in the state directory where `--save-output` saves output.
+/// It is kept apart from the test cases such that the output is never taken
+/// for an answer.
+const OUTPUT_DIR_NAME: &str = "output";
+
+/// Where the output of the named test case is saved. Test cases in
+/// subfolders are saved in the same subfolders.
+fn saved_output_path(output_dir: &Path, test_name: &str) -> PathBuf {
+    output_dir.join(format!("{}.out", test_name))
+}
+
+/// Prints the output of a test case that has no answer, or where it was saved.
+fn print_unchecked_output(result: &TestResult, options: &TestOptions) {
+    if let Some(dir) = &options.output_dir {
+        let path = saved_output_path(dir, &result.name);
+        println!("output saved to {}\n", path.display());
+        return;
+    }
+
+    let output = reformat_ans_str(&result.output.stdout);
+    let lines = output.lines().collect::<Vec<_>>();
     println!(
-        "\ntest result: {}. {} passed; {} failed.",
-        test_result, num_passed, fails
+        "{}\n{}",
+        "Output:".underline(),
+        lines[..lines.len().min(OUTPUT_PREVIEW_LINES)].join("\n")
     );
 
-    Ok(())
+    if lines.len() > OUTPUT_PREVIEW_LINES {
+        println!(
+            "... ({} more lines, use --save-output to see everything)",
+            lines.len() - OUTPUT_PREVIEW_LINES
+        );
+    }
+
+    println!();
 }
 
-fn reformat_ans_str(s: &str) -> String {
-    s.replace("\r\n", "\n")
-        .trim_end()
-        .lines()
-        .map(str::trim_end)
-        .collect::<Vec<&str>>()
-        .join("\n")
+/// Prints the highest CPU time and memory usage of any test case, along with the
+/// limits they count towards.
+fn print_max_usage(usage: &Usage, options: &TestOptions) {
+    let memory_limit = match options.limits.memory {
+        Some(m) if m != UNLIMITED => format!(" of {}", format_megabytes(m)),
+        _ => String::new(),
+    };
+
+    println!(
+        "max CPU time: {:.2}s of {:.2}s. max memory: {}{}.",
+        usage.cpu_time.as_secs_f64(),
+        options.time_limit.as_secs_f64(),
+        format_megabytes(usage.peak_memory),
+        memory_limit
+    );
 }
 
-fn watch<F: Fn() -> Result<(), StdErr>>(problem: &Problem, test_runner: F) -> Result<(), StdErr> {
-    let (tx, rx) = channel();
-    let mut watcher = watcher(tx, Duration::from_secs(1)).unwrap();
+fn format_megabytes(bytes: u64) -> String {
+    format!("{:.1} MB", bytes as f64 / MEGABYTE as f64)
+}
 
-    let src_file = problem.file();
-    watcher.watch(&src_file, RecursiveMode::NonRecursive)?;
+/// The number of lines shown from the end of the transcript of a failed
+/// interactive test.
+const TRANSCRIPT_TAIL_LINES: usize = 20;
 
-    let test_runner_wrapper = || {
-        if let Err(e) = test_runner() {
-            eprintln!("{}: {}", "error".bright_red(), e);
-        }
+fn print_interaction(result: &TestResult, transcript: &str) {
+    if let Some(message) = &result.judge_message {
+        println!("{}\n{}\n", "Judge message:".underline(), message);
+    }
 
-        println!(
-            "\n{} {}...\n",
-            "watching".bright_cyan(),
-            src_file
-                .file_name()
-                .expect("couldn't read file name")
-                .to_string_lossy()
-                .underline(),
-        );
+    if let (Some(Verdict::RunTimeError | Verdict::MemoryLimitExceeded), Some(status)) =
+        (result.verdict, &result.output.status)
+    {
+        println!("{}\n", verdict::exit_reason(status));
+        print_stream("Stderr:", &result.output.stderr);
+    }
+
+    let lines = transcript.lines().collect::<Vec<_>>();
+    let tail = &lines[lines.len().saturating_sub(TRANSCRIPT_TAIL_LINES)..];
+    let title = if tail.len() < lines.len() {
+        format!("Transcript (last {} lines):", tail.len())
+    } else {
+        "Transcript:".to_string()
     };
+    println!("{}\n{}\n", title.underline(), tail.join("\n"));
 
-    test_runner_wrapper();
-    loop {
-        match rx.recv() {
-            Ok(event) => {
-                if let DebouncedEvent::NoticeWrite(_) = event {
-                    test_runner_wrapper();
-                }
-            }
-            Err(_) => return Err("something went wrong during file watching".into()),
-        }
+    let sample = result.input.with_extension("interaction");
+    if let Ok(sample) = fs::read_to_string(sample) {
+        println!(
+            "{}\n{}\n",
+            "Sample interaction:".underline(),
+            sample.trim_end()
+        );
     }
 }
+
+fn reformat_ans_str(s: &[u8]) -> String {
+    compare::lines(s)
+        .map(String::from_utf8_lossy)
+        .collect::<Vec<_>>()
+        .join("\n")
+}
@@ -0,0 +1,404 @@
+use crate::problem::{Problem, TestCase, CUSTOM_TEST_DIR_NAME, TEST_DIR_NAME};
+use crate::test_layout::TestLayout;
+use crate::utils::prompt_bool;
+use crate::StdErr;
+use clap::ArgMatches;
+use colored::Colorize;
+use std::env;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+#[cfg(not(windows))]
+const DEFAULT_EDITOR: &str = "vi";
+#[cfg(windows)]
+const DEFAULT_EDITOR: &str = "notepad";
+
+#[cfg(not(windows))]
+const DEFAULT_PAGER: Option<&str> = Some("less -FRX");
+#[cfg(windows)]
+const DEFAULT_PAGER: Option<&str> = None;
+
+pub async fn tests(cmd: &ArgMatches<'_>) -> Result<(), StdErr> {
+    let (name, sub) = cmd.subcommand();
+    let sub = match sub {
+        Some(s) => s,
+        None => return Ok(()),
+    };
+
+    // The path is a global argument, so it may be given before or after the
+    // name of the subcommand.
+    let path_arg = sub.value_of("path").or(cmd.value_of("path")).unwrap_or(".");
+    let problem_dir = Problem::get_path(path_arg)?;
+
+    match name {
+        "add" => add(&problem_dir, sub),
+        "list" => list(&problem_dir),
+        "show" => show(&problem_dir, sub),
+        "rm" => remove(&problem_dir, sub),
+        "rename" => rename(&problem_dir, sub),
+        _ => Ok(()),
+    }
+}
+
+fn add(problem_dir: &Path, cmd: &ArgMatches<'_>) -> Result<(), StdErr> {
+    let custom_dir = problem_dir.join(TEST_DIR_NAME).join(CUSTOM_TEST_DIR_NAME);
+    let layout = TestLayout::for_problem(problem_dir)?;
+
+    let name = match cmd.value_of("NAME") {
+        Some(n) => custom_name(n)?,
+        None => next_free_name(&custom_dir, layout.input_extension()),
+    };
+
+    let input_path = custom_dir.join(format!("{}.{}", name, layout.input_extension()));
+    if input_path.exists() {
+        return Err(format!("a custom test named \"{}\" already exists", name).into());
+    }
+
+    let input_source = cmd.value_of("in");
+    let answer_source = cmd.value_of("ans");
+    if input_source == Some("-") && answer_source == Some("-") {
+        return Err("only one of the input and the answer can be read from stdin".into());
+    }
+
+    let input = read_source(input_source, "input")?;
+    let answer = if cmd.is_present("no-ans") {
+        None
+    } else {
+        Some(read_source(answer_source, "answer")?)
+    };
+
+    if fs::create_dir_all(&custom_dir).is_err() {
+        return Err(format!("failed to create {}", custom_dir.display()).into());
+    }
+
+    write_file(&input_path, &input)?;
+    if let Some(answer) = answer {
+        let answer_path = custom_dir.join(format!("{}.{}", name, layout.answer_extension()));
+        write_file(&answer_path, &answer)?;
+    }
+
+    println!(
+        "{} custom test \"{}/{}\"",
+        "created".bright_green(),
+        CUSTOM_TEST_DIR_NAME,
+        name
+    );
+
+    Ok(())
+}
+
+fn list(problem_dir: &Path) -> Result<(), StdErr> {
+    let tests = Problem::find_tests(problem_dir)?;
+
+    if tests.is_empty() {
+        println!("no test cases found");
+        return Ok(());
+    }
+
+    let name_width = tests
+        .iter()
+        .map(|t| t.name.len())
+        .max()
+        .unwrap_or(0)
+        .max("Name".len());
+
+    println!(
+        "{:name_width$}  {:6}  {:>9}  {:>9}",
+        "Name".bright_cyan(),
+        "Type".bright_cyan(),
+        "Input".bright_cyan(),
+        "Answer".bright_cyan(),
+        name_width = name_width
+    );
+
+    for test in &tests {
+        let kind = if test.custom { "custom" } else { "sample" };
+        let answer_size = match &test.answer {
+            Some(a) => format_size(fs::metadata(a)?.len()),
+            None => "-".to_string(),
+        };
+
+        println!(
+            "{:name_width$}  {:6}  {:>9}  {:>9}",
+            test.name,
+            kind,
+            format_size(fs::metadata(&test.input)?.len()),
+            answer_size,
+            name_width = name_width
+        );
+    }
+
+    Ok(())
+}
+
+fn show(problem_dir: &Path, cmd: &ArgMatches<'_>) -> Result<(), StdErr> {
+    // We can unwrap because clap requires the argument.
+    let test = find_test(problem_dir, cmd.value_of("TEST").unwrap())?;
+
+    let input = fs::read(&test.input)?;
+    let answer = match &test.answer {
+        Some(a) => String::from_utf8_lossy(&fs::read(a)?).into_owned(),
+        None => "(no answer)".to_string(),
+    };
+
+    let text = format!(
+        "{}\n{}\n\n{}\n{}\n",
+        "Input:".underline(),
+        String::from_utf8_lossy(&input).trim_end(),
+        "Answer:".underline(),
+        answer.trim_end()
+    );
+
+    page(&text)
+}
+
+fn remove(problem_dir: &Path, cmd: &ArgMatches<'_>) -> Result<(), StdErr> {
+    // We can unwrap because clap requires at least one test.
+    for name in cmd.values_of("TESTS").unwrap() {
+        let test = find_test(problem_dir, name)?;
+
+        if !test.custom
+            && !cmd.is_present("yes")
+            && !prompt_bool(&format!(
+                "\"{}\" is an official sample. do you want to remove it?",
+                test.name
+            ))
+        {
+            continue;
+        }
+
+        let mut files = vec![test.input.clone()];
+        files.extend(test.answer.clone());
+
+        for file in files {
+            if fs::remove_file(&file).is_err() {
+                return Err(format!("failed to remove {}", file.display()).into());
+            }
+        }
+
+        println!("{} test \"{}\"", "removed".bright_green(), test.name);
+    }
+
+    Ok(())
+}
+
+fn rename(problem_dir: &Path, cmd: &ArgMatches<'_>) -> Result<(), StdErr> {
+    // We can unwrap because clap requires both arguments.
+    let test = find_test(problem_dir, cmd.value_of("OLD").unwrap())?;
+    let new_name = custom_name(cmd.value_of("NEW").unwrap())?;
+
+    if !test.custom {
+        return Err("only custom tests can be renamed".into());
+    }
+
+    // The files keep their extensions.
+    let renamed = |path: &Path| match path.extension() {
+        Some(ext) => path.with_file_name(format!("{}.{}", new_name, ext.to_string_lossy())),
+        None => path.with_file_name(&new_name),
+    };
+
+    let new_input = renamed(&test.input);
+    if new_input.exists() {
+        return Err(format!("a custom test named \"{}\" already exists", new_name).into());
+    }
+
+    let mut moves = vec![(test.input.clone(), new_input)];
+    if let Some(answer) = &test.answer {
+        moves.push((answer.clone(), renamed(answer)));
+    }
+
+    for (from, to) in moves {
+        if fs::rename(&from, &to).is_err() {
+            return Err(format!("failed to rename {}", from.display()).into());
+        }
+    }
+
+    println!(
+        "{} test \"{}\" to \"{}/{}\"",
+        "renamed".bright_green(),
+        test.name,
+        CUSTOM_TEST_DIR_NAME,
+        new_name
+    );
+
+    Ok(())
+}
+
+fn find_test(problem_dir: &Path, name: &str) -> Result<TestCase, StdErr> {
+    match Problem::find_tests(problem_dir)?
+        .into_iter()
+        .find(|t| t.name == name)
+    {
+        Some(t) => Ok(t),
+        None => Err(format!("there is no test named \"{}\"", name).into()),
+    }
+}
+
+/// Checks that a name given for a custom test can be used as a file name. The
+/// "custom/" prefix that custom tests are listed with may be left out.
+fn custom_name(name: &str) -> Result<String, StdErr> {
+    let prefix = format!("{}/", CUSTOM_TEST_DIR_NAME);
+    let name = name.strip_prefix(&prefix).unwrap_or(name);
+
+    let is_legal = !name.is_empty()
+        && !name.starts_with('.')
+        && name
+            .chars()
+            .all(|c| c.is_alphanumeric() || matches!(c, '-' | '_' | '.'));
+
+    if !is_legal {
+        return Err(format!(
+            "\"{}\" is not a valid test name. use only letters, digits, '-', '_' and '.'",
+            name
+        )
+        .into());
+    }
+
+    Ok(name.to_string())
+}
+
+/// Finds the number after the highest numbered custom test.
+fn next_free_name(custom_dir: &Path, input_extension: &str) -> String {
+    let entries = match fs::read_dir(custom_dir) {
+        Ok(e) => e,
+        Err(_) => return "1".to_string(),
+    };
+
+    let highest = entries
+        .flatten()
+        .filter_map(|entry| {
+            let path = entry.path();
+            if path.extension()? != input_extension {
+                return None;
+            }
+
+            path.file_stem()?.to_str()?.parse::<u64>().ok()
+        })
+        .max()
+        .unwrap_or(0);
+
+    (highest + 1).to_string()
+}
+
+/// Reads the input or answer of a new test from a file, from stdin if the
+/// source is "-", or from an editor if no source is given.
+fn read_source(source: Option<&str>, what: &str) -> Result<Vec<u8>, StdErr> {
+    match source {
+        Some("-") => {
+            let mut content = Vec::new();
+            if io::stdin().read_to_end(&mut content).is_err() {
+                return Err(format!("failed to read the {} from stdin", what).into());
+            }
+            Ok(content)
+        }
+        Some(path) => match fs::read(path) {
+            Ok(c) => Ok(c),
+            Err(_) => Err(format!("failed to read the {} from {}", what, path).into()),
+        },
+        None => edit(what),
+    }
+}
+
+/// Opens $VISUAL or $EDITOR on an empty file and gives what was written in it.
+fn edit(what: &str) -> Result<Vec<u8>, StdErr> {
+    let editor = env::var("VISUAL")
+        .or_else(|_| env::var("EDITOR"))
+        .unwrap_or_else(|_| DEFAULT_EDITOR.to_string());
+
+    let mut cmd = match shlex::split(&editor) {
+        Some(c) if !c.is_empty() => c,
+        _ => return Err(format!("failed to parse editor command: {}", editor).into()),
+    };
+
+    let file = tempfile::Builder::new()
+        .prefix(&format!("kitty-{}-", what))
+        .suffix(".txt")
+        .tempfile()?;
+    cmd.push(file.path().to_string_lossy().into_owned());
+
+    println!("write the {} in {} and close it to continue", what, cmd[0]);
+
+    match Command::new(&cmd[0]).args(&cmd[1..]).status() {
+        Ok(status) if status.success() => {}
+        Ok(_) => return Err("the editor exited with an error".into()),
+        Err(_) => return Err(format!("failed to start editor: {}", editor).into()),
+    }
+
+    let content = fs::read(file.path())?;
+    if content.iter().all(u8::is_ascii_whitespace) {
+        return Err(format!("aborted since the {} is empty", what).into());
+    }
+
+    Ok(content)
+}
+
+/// Prints text through $PAGER if stdout is a terminal, or directly otherwise.
+fn page(text: &str) -> Result<(), StdErr> {
+    let pager = env::var("PAGER")
+        .ok()
+        .or_else(|| DEFAULT_PAGER.map(String::from));
+
+    let cmd = match pager.as_deref().and_then(shlex::split) {
+        Some(c) if !c.is_empty() && is_terminal() => c,
+        _ => {
+            print!("{}", text);
+            return Ok(());
+        }
+    };
+
+    let mut child = match Command::new(&cmd[0])
+        .args(&cmd[1..])
+        .stdin(Stdio::piped())
+        .spawn()
+    {
+        Ok(c) => c,
+        Err(_) => {
+            print!("{}", text);
+            return Ok(());
+        }
+    };
+
+    // The pager closes its stdin if it is quit early, which is not an error.
+    if let Some(mut stdin) = child.stdin.take() {
+        let _ = stdin.write_all(text.as_bytes());
+    }
+
+    if child.wait().is_err() {
+        return Err("failed to wait for the pager to exit".into());
+    }
+
+    Ok(())
+}
+
+#[cfg(unix)]
+fn is_terminal() -> bool {
+    unsafe { libc::isatty(libc::STDOUT_FILENO) == 1 }
+}
+
+#[cfg(not(unix))]
+fn is_terminal() -> bool {
+    false
+}
+
+fn format_size(bytes: u64) -> String {
+    const KILOBYTE: f64 = 1024.0;
+
+    let bytes_f = bytes as f64;
+    if bytes_f < KILOBYTE {
+        format!("{} B", bytes)
+    } else if bytes_f < KILOBYTE * KILOBYTE {
+        format!("{:.1} KB", bytes_f / KILOBYTE)
+    } else {
+        format!("{:.1} MB", bytes_f / (KILOBYTE * KILOBYTE))
+    }
+}
+
+fn write_file(path: &Path, content: &[u8]) -> Result<(), StdErr> {
+    if fs::write(path, content).is_err() {
+        return Err(format!("failed to write {}", path.display()).into());
+    }
+
+    Ok(())
+}
@@ -0,0 +1,380 @@
+use crate::verdict::Verdict;
+use crate::StdErr;
+
+/// How much numbers in the output may deviate from the answer. This mirrors
+/// the float flags of Kattis' default output validator: a number is accepted if
+/// it is within either of the tolerances.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct FloatTolerance {
+    pub absolute: Option<f64>,
+    pub relative: Option<f64>,
+}
+
+impl FloatTolerance {
+    /// Reads the tolerance from flags given in the format of Kattis' default
+    /// output validator, such as `float_tolerance 1e-6`. Unrelated flags are
+    /// ignored.
+    pub fn from_validator_flags(flags: &[String]) -> Result<Self, StdErr> {
+        let mut tolerance = Self::default();
+        let mut flags = flags.iter();
+
+        while let Some(flag) = flags.next() {
+            let is_float_flag = matches!(
+                flag.as_str(),
+                "float_tolerance" | "float_absolute_tolerance" | "float_relative_tolerance"
+            );
+
+            if !is_float_flag {
+                continue;
+            }
+
+            let value = match flags.next().and_then(|v| v.parse::<f64>().ok()) {
+                Some(v) => v,
+                None => {
+                    return Err(
+                        format!("validator flag {} must be followed by a number", flag).into(),
+                    )
+                }
+            };
+
+            match flag.as_str() {
+                "float_absolute_tolerance" => tolerance.absolute = Some(value),
+                "float_relative_tolerance" => tolerance.relative = Some(value),
+                _ => {
+                    tolerance.absolute = Some(value);
+                    tolerance.relative = Some(value);
+                }
+            }
+        }
+
+        Ok(tolerance)
+    }
+
+    pub fn is_set(&self) -> bool {
+        self.absolute.is_some() || self.relative.is_some()
+    }
+
+    fn accepts(&self, expected: f64, actual: f64) -> bool {
+        let diff = (expected - actual).abs();
+
+        self.absolute.is_some_and(|t| diff <= t)
+            || self.relative.is_some_and(|t| diff <= t * expected.abs())
+    }
+}
+
+/// How the output of a program is compared to the expected answer.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum CompareMode {
+    /// The output must match the answer byte for byte.
+    Exact,
+    /// Whitespace at the ends of lines and blank lines at the end are ignored.
+    #[default]
+    Lines,
+    /// Only the whitespace-separated tokens are compared, like Kattis' default
+    /// output validator does.
+    Tokens,
+    /// Like `Tokens`, but letters may differ in case.
+    CaseInsensitive,
+}
+
+impl CompareMode {
+    pub const NAMES: &'static [&'static str] = &["exact", "lines", "tokens", "case-insensitive"];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "exact" => Some(CompareMode::Exact),
+            "lines" => Some(CompareMode::Lines),
+            "tokens" => Some(CompareMode::Tokens),
+            "case-insensitive" => Some(CompareMode::CaseInsensitive),
+            _ => None,
+        }
+    }
+}
+
+/// Everything that decides whether an output matches the answer.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Comparison {
+    pub mode: CompareMode,
+    pub tolerance: FloatTolerance,
+}
+
+impl Comparison {
+    /// Whether only tokens are compared. A float tolerance implies this, since
+    /// numbers within the tolerance may be written differently.
+    fn compares_tokens(&self) -> bool {
+        self.tolerance.is_set()
+            || matches!(
+                self.mode,
+                CompareMode::Tokens | CompareMode::CaseInsensitive
+            )
+    }
+}
+
+/// Compares the output of a program to the expected answer. In the modes that
+/// care about whitespace, an output that only differs from the answer in its
+/// whitespace is judged as a presentation warning rather than a wrong answer.
+///
+/// If a float tolerance is given, the output is compared token by token
+/// regardless of the mode, like Kattis' default output validator does. Tokens
+/// that are numbers in the answer are compared with the tolerance, and all
+/// other tokens must match.
+pub fn compare(answer: &[u8], output: &[u8], comparison: Comparison) -> Verdict {
+    let accepted = if comparison.compares_tokens() {
+        tokens_match(answer, output, comparison)
+    } else if comparison.mode == CompareMode::Exact {
+        answer == output
+    } else {
+        lines(answer).eq(lines(output))
+    };
+
+    if accepted {
+        Verdict::Accepted
+    } else if tokens(answer).eq(tokens(output)) {
+        Verdict::PresentationWarning
+    } else {
+        Verdict::WrongAnswer
+    }
+}
+
+/// Whether a line of the output matches the corresponding line of the answer
+/// in the given comparison.
+pub fn lines_match(expected: &[u8], actual: &[u8], comparison: Comparison) -> bool {
+    if comparison.compares_tokens() {
+        tokens_match(expected, actual, comparison)
+    } else if comparison.mode == CompareMode::Exact {
+        expected == actual
+    } else {
+        trim_end(expected) == trim_end(actual)
+    }
+}
+
+/// Splits the text into lines without their trailing whitespace. Blank lines at
+/// the end of the text are left out.
+pub fn lines(text: &[u8]) -> impl Iterator<Item = &[u8]> {
+    trim_end(text).split(|&b| b == b'\n').map(trim_end)
+}
+
+/// Splits the text into tokens separated by whitespace.
+fn tokens(text: &[u8]) -> impl Iterator<Item = &[u8]> {
+    text.split(|b| b.is_ascii_whitespace())
+        .filter(|t| !t.is_empty())
+}
+
+fn tokens_match(answer: &[u8], output: &[u8], comparison: Comparison) -> bool {
+    let mut answer_tokens = tokens(answer);
+    let mut output_tokens = tokens(output);
+
+    loop {
+        match (answer_tokens.next(), output_tokens.next()) {
+            (None, None) => return true,
+            (Some(expected), Some(actual)) if token_matches(expected, actual, comparison) => {}
+            _ => return false,
+        }
+    }
+}
+
+pub fn token_matches(expected: &[u8], actual: &[u8], comparison: Comparison) -> bool {
+    let tolerance = comparison.tolerance;
+
+    if tolerance.is_set() {
+        match (parse_float(expected), parse_float(actual)) {
+            (Some(e), Some(a)) => return expected == actual || tolerance.accepts(e, a),
+            (Some(_), None) => return false,
+            _ => {}
+        }
+    }
+
+    if comparison.mode == CompareMode::CaseInsensitive {
+        expected.eq_ignore_ascii_case(actual)
+    } else {
+        expected == actual
+    }
+}
+
+fn parse_float(token: &[u8]) -> Option<f64> {
+    std::str::from_utf8(token)
+        .ok()?
+        .parse::<f64>()
+        .ok()
+        .filter(|f| f.is_finite())
+}
+
+fn trim_end(mut text: &[u8]) -> &[u8] {
+    while let [rest @ .., last] = text {
+        if !last.is_ascii_whitespace() {
+            break;
+        }
+
+        text = rest;
+    }
+
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn with_mode(mode: CompareMode) -> Comparison {
+        Comparison {
+            mode,
+            tolerance: FloatTolerance::default(),
+        }
+    }
+
+    fn with_tolerance(absolute: Option<f64>, relative: Option<f64>) -> Comparison {
+        Comparison {
+            mode: CompareMode::default(),
+            tolerance: FloatTolerance { absolute, relative },
+        }
+    }
+
+    #[test]
+    fn numbers_within_tolerance_are_accepted() {
+        let comparison = with_tolerance(Some(1e-6), Some(1e-6));
+
+        assert_eq!(
+            compare(b"0.333333333\n", b"0.3333333\n", comparison),
+            Verdict::Accepted
+        );
+        assert_eq!(
+            compare(b"0.333333333\n", b"0.333\n", comparison),
+            Verdict::WrongAnswer
+        );
+    }
+
+    #[test]
+    fn either_tolerance_is_enough() {
+        // Off by 0.5, which is 0.05% of the answer.
+        let (answer, output) = (b"1000\n", b"1000.5\n");
+
+        let absolute = with_tolerance(Some(1e-6), None);
+        let relative = with_tolerance(None, Some(1e-3));
+        let both = with_tolerance(Some(1e-6), Some(1e-3));
+
+        assert_eq!(compare(answer, output, absolute), Verdict::WrongAnswer);
+        assert_eq!(compare(answer, output, relative), Verdict::Accepted);
+        assert_eq!(compare(answer, output, both), Verdict::Accepted);
+    }
+
+    #[test]
+    fn tolerance_only_applies_to_numbers_in_the_answer() {
+        let comparison = with_tolerance(Some(1e-6), Some(1e-6));
+
+        assert_eq!(
+            compare(b"1.0 yes\n", b"1.0000001 yes\n", comparison),
+            Verdict::Accepted
+        );
+        assert_eq!(
+            compare(b"1.0\n", b"one\n", comparison),
+            Verdict::WrongAnswer
+        );
+        assert_eq!(
+            compare(b"yes\n", b"Yes\n", comparison),
+            Verdict::WrongAnswer
+        );
+        assert_eq!(compare(b"nan\n", b"nan\n", comparison), Verdict::Accepted);
+    }
+
+    #[test]
+    fn tolerance_is_read_from_validator_flags() {
+        let flags = |f: &[&str]| f.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+
+        let tolerance = FloatTolerance::from_validator_flags(&flags(&[
+            "case_sensitive",
+            "float_tolerance",
+            "1e-6",
+        ]))
+        .unwrap();
+        assert_eq!(tolerance.absolute, Some(1e-6));
+        assert_eq!(tolerance.relative, Some(1e-6));
+
+        let tolerance =
+            FloatTolerance::from_validator_flags(&flags(&["float_relative_tolerance", "0.01"]))
+                .unwrap();
+        assert_eq!(tolerance.absolute, None);
+        assert_eq!(tolerance.relative, Some(0.01));
+
+        assert!(
+            FloatTolerance::from_validator_flags(&flags(&["float_absolute_tolerance"])).is_err()
+        );
+    }
+
+    #[test]
+    fn exact_mode_needs_every_byte() {
+        let exact = with_mode(CompareMode::Exact);
+
+        assert_eq!(compare(b"1 2\n", b"1 2\n", exact), Verdict::Accepted);
+        assert_eq!(
+            compare(b"1 2\n", b"1 2", exact),
+            Verdict::PresentationWarning
+        );
+        assert_eq!(
+            compare(b"1 2\n", b"1 2\r\n", exact),
+            Verdict::PresentationWarning
+        );
+        assert_eq!(compare(b"1 2\n", b"1 3\n", exact), Verdict::WrongAnswer);
+    }
+
+    #[test]
+    fn lines_mode_ignores_whitespace_at_line_ends() {
+        let lines = with_mode(CompareMode::Lines);
+
+        assert_eq!(
+            compare(b"1 2\n3\n", b"1 2  \r\n3\r\n\n\n", lines),
+            Verdict::Accepted
+        );
+        assert_eq!(compare(b"1 2\n3\n", b"1 2\n3", lines), Verdict::Accepted);
+        assert_eq!(
+            compare(b"1 2\n3\n", b"1  2\n3\n", lines),
+            Verdict::PresentationWarning
+        );
+        assert_eq!(
+            compare(b"1 2\n3\n", b"1 2 3\n", lines),
+            Verdict::PresentationWarning
+        );
+        assert_eq!(
+            compare(b"1 2\n3\n", b"1 2\n4\n", lines),
+            Verdict::WrongAnswer
+        );
+    }
+
+    #[test]
+    fn tokens_mode_ignores_all_whitespace() {
+        let tokens = with_mode(CompareMode::Tokens);
+
+        assert_eq!(compare(b"1 2\n3\n", b" 1\n2\t3", tokens), Verdict::Accepted);
+        assert_eq!(compare(b"YES\n", b"yes\n", tokens), Verdict::WrongAnswer);
+        assert_eq!(compare(b"1 2\n", b"1 2 3\n", tokens), Verdict::WrongAnswer);
+    }
+
+    #[test]
+    fn case_insensitive_mode_ignores_case_of_letters() {
+        let case_insensitive = with_mode(CompareMode::CaseInsensitive);
+
+        assert_eq!(
+            compare(b"YES\n", b"yes\n", case_insensitive),
+            Verdict::Accepted
+        );
+        assert_eq!(
+            compare(b"YES\n", b"yes no\n", case_insensitive),
+            Verdict::WrongAnswer
+        );
+    }
+
+    #[test]
+    fn lines_match_follows_the_mode() {
+        assert!(lines_match(b"1 2", b"1 2  ", with_mode(CompareMode::Lines)));
+        assert!(!lines_match(
+            b"1 2",
+            b"1 2  ",
+            with_mode(CompareMode::Exact)
+        ));
+        assert!(lines_match(b"1 2", b"1  2", with_mode(CompareMode::Tokens)));
+        assert!(lines_match(
+            b"0.5",
+            b"0.5000001",
+            with_tolerance(Some(1e-6), None)
+        ));
+    }
+}
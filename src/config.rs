@@ -1,6 +1,10 @@
+use crate::compare::CompareMode;
 use crate::lang::Language;
-use crate::utils::path_to_str;
+use crate::problem::STATE_DIR_NAME;
+use crate::test_layout::LayoutSettings;
+use crate::utils::{path_to_str, stable_hash};
 use crate::StdErr;
+use crate::CFG as cfg;
 use ini::{Ini, Properties};
 use platform_dirs::AppDirs;
 use std::env::consts::EXE_EXTENSION;
@@ -9,9 +13,38 @@ use std::fs;
 use std::io;
 use std::path::{Path, PathBuf};
 
+/// The name of the directory that compiled programs are written to, inside
+/// either a problem's state directory or kitty's cache directory.
+const BUILD_DIR_NAME: &str = "build";
+
+/// Where compiled programs and other build output are written.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum BuildLocation {
+    /// In the `.kitty` directory of each problem.
+    #[default]
+    Problem,
+    /// In kitty's cache directory, outside the problem directories.
+    Cache,
+}
+
+impl BuildLocation {
+    pub const NAMES: &'static [&'static str] = &["problem", "cache"];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "problem" => Some(BuildLocation::Problem),
+            "cache" => Some(BuildLocation::Cache),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Default, Debug)]
 pub struct Config {
     default_language: Option<String>,
+    default_compare_mode: Option<CompareMode>,
+    test_layout: LayoutSettings,
+    build_location: BuildLocation,
     languages: Vec<Language>,
     kattisrc: Option<Kattisrc>,
 }
@@ -61,6 +94,21 @@ impl Config {
             .and_then(|l| self.lang_from_file_ext(l))
     }
 
+    /// The compare mode used by `kitty test` when none is given.
+    pub fn default_compare_mode(&self) -> CompareMode {
+        self.default_compare_mode.unwrap_or_default()
+    }
+
+    /// Where to find test cases unless a problem says otherwise.
+    pub fn test_layout(&self) -> &LayoutSettings {
+        &self.test_layout
+    }
+
+    /// Where compiled programs are written.
+    pub fn build_location(&self) -> BuildLocation {
+        self.build_location
+    }
+
     /// Gets kitty's config directory. The location of this directory will vary
     /// by platform:
     ///  - `%APPDATA%/kitty` on Windows
@@ -72,6 +120,15 @@ impl Config {
             .config_dir
     }
 
+    /// Gets the directory in kitty's cache directory that holds the build
+    /// output of every problem when `build_location` is `cache`.
+    pub fn build_cache_path() -> PathBuf {
+        AppDirs::new(Some("kitty"), false)
+            .expect("failed to find where the kitty cache directory should be located")
+            .cache_dir
+            .join(BUILD_DIR_NAME)
+    }
+
     /// Retrieves the path to the directory containing user-defined templates.
     pub fn templates_dir_path() -> PathBuf {
         Self::dir_path().join("templates")
@@ -93,7 +150,14 @@ impl Config {
 }
 
 mod config_parser {
-    use crate::{config::Config, lang::Language, StdErr};
+    use crate::{
+        compare::CompareMode,
+        config::{BuildLocation, Config},
+        lang::{Language, Profile},
+        process::UNLIMITED,
+        test_layout::LayoutSettings,
+        StdErr,
+    };
     use yaml_rust::{Yaml, YamlLoader};
 
     #[cfg(unix)]
@@ -110,6 +174,33 @@ mod config_parser {
         };
 
         let default_language = doc["default_language"].as_str().map(str::to_string);
+        let default_compare_mode = match doc["default_compare_mode"].as_str() {
+            Some(m) => match CompareMode::from_name(m) {
+                Some(mode) => Some(mode),
+                None => {
+                    return Err(format!(
+                        "default_compare_mode in the config file must be one of: {}",
+                        CompareMode::NAMES.join(", ")
+                    )
+                    .into())
+                }
+            },
+            None => None,
+        };
+        let test_layout = LayoutSettings::from_yaml(&doc["tests"], "the config file")?;
+        let build_location = match doc["build_location"].as_str() {
+            Some(l) => match BuildLocation::from_name(l) {
+                Some(location) => location,
+                None => {
+                    return Err(format!(
+                        "build_location in the config file must be one of: {}",
+                        BuildLocation::NAMES.join(", ")
+                    )
+                    .into())
+                }
+            },
+            None => Default::default(),
+        };
         let languages = doc["languages"]
             .as_vec()
             .map(|v| {
@@ -121,6 +212,9 @@ mod config_parser {
 
         let config = Config {
             default_language,
+            default_compare_mode,
+            test_layout,
+            build_location,
             languages,
             ..Default::default()
         };
@@ -133,8 +227,84 @@ mod config_parser {
         let file_ext = get_value_else_err("file_extension", lang_block)?;
         let run_cmd = get_value_else_err("run_command", lang_block)?;
         let compile_cmd = get_string_value("compile_command", lang_block);
+        let profiles = profiles_from_yml(&name, lang_block)?;
+        let default_profile = get_string_value("default_profile", lang_block);
+
+        if let Some(default) = &default_profile {
+            if !profiles.iter().any(|p| p.name == *default) {
+                return Err(format!(
+                    "the default_profile of {} in the config file must be one of its profiles",
+                    name
+                )
+                .into());
+            }
+        }
 
-        Ok(Language::new(name, file_ext, run_cmd, compile_cmd))
+        Ok(Language::new(
+            name,
+            file_ext,
+            run_cmd,
+            compile_cmd,
+            profiles,
+            default_profile,
+        ))
+    }
+
+    /// Reads the named profiles of a language, which may override its compile
+    /// and run commands, per platform too.
+    fn profiles_from_yml(lang_name: &str, lang_block: &Yaml) -> Result<Vec<Profile>, StdErr> {
+        let profiles = match &lang_block["profiles"] {
+            Yaml::BadValue => return Ok(Vec::new()),
+            Yaml::Hash(h) => h,
+            _ => {
+                return Err(format!(
+                    "the profiles of {} in the config file must be a set of names",
+                    lang_name
+                )
+                .into())
+            }
+        };
+
+        profiles
+            .iter()
+            .map(|(name, block)| match name.as_str() {
+                Some(n) if block.as_hash().is_some() => Ok(Profile {
+                    name: n.to_string(),
+                    run_cmd: get_string_value("run_command", block),
+                    compile_cmd: get_string_value("compile_command", block),
+                    memory_limit: profile_memory_limit(lang_name, n, block)?,
+                }),
+                _ => Err(format!(
+                    "the profiles of {} in the config file must each contain commands",
+                    lang_name
+                )
+                .into()),
+            })
+            .collect()
+    }
+
+    /// Reads the memory limit of a profile, given in megabytes or as
+    /// "unlimited", and gives it in bytes.
+    fn profile_memory_limit(
+        lang_name: &str,
+        profile_name: &str,
+        block: &Yaml,
+    ) -> Result<Option<u64>, StdErr> {
+        let limit = match get_value("memory_limit", block) {
+            None | Some(Yaml::BadValue) => return Ok(None),
+            Some(Yaml::String(s)) if s == "unlimited" => Some(UNLIMITED),
+            Some(Yaml::Integer(mb)) if mb >= 0 => (mb as u64).checked_mul(1024 * 1024),
+            Some(_) => None,
+        };
+
+        match limit {
+            Some(l) => Ok(Some(l)),
+            None => Err(format!(
+                "the memory_limit of profile {} of {} in the config file must be a number of megabytes or \"unlimited\"",
+                profile_name, lang_name
+            )
+            .into()),
+        }
     }
 
     fn get_value_else_err(key: &str, doc: &Yaml) -> Result<String, StdErr> {
@@ -162,10 +332,53 @@ mod config_parser {
     }
 }
 
-pub fn prepare_cmd(cmd: &str, file_path: &Path) -> Option<Vec<String>> {
+/// The directory that the build output of the source files in `dir_path` is
+/// written to, as given by `$BUILD_DIR` in commands.
+pub fn build_dir(dir_path: &Path) -> PathBuf {
+    match cfg.build_location() {
+        BuildLocation::Problem => dir_path.join(STATE_DIR_NAME).join(BUILD_DIR_NAME),
+        BuildLocation::Cache => {
+            // Problems with the same name in different places must not share
+            // a build directory, so the full path is part of the name.
+            let dir_path = dir_path
+                .canonicalize()
+                .unwrap_or_else(|_| dir_path.to_path_buf());
+            let hash = stable_hash(dir_path.to_string_lossy().as_bytes());
+
+            let name = dir_path
+                .file_name()
+                .map_or_else(String::new, |n| n.to_string_lossy().into_owned());
+
+            Config::build_cache_path().join(format!("{}-{:016x}", name, hash))
+        }
+    }
+}
+
+/// Where the program compiled from a source file by `compile_cmd` is written,
+/// as given by `$EXE_PATH` in commands.
+pub fn exe_path(file_path: &Path, compile_cmd: Option<&str>) -> PathBuf {
+    let exe_path = file_path.with_extension(EXE_EXTENSION);
+
+    // Compile commands that know nothing of the build directory, like those
+    // written before it existed, leave the program next to the source file.
+    let uses_build_dir =
+        compile_cmd.is_some_and(|c| c.contains("$EXE_PATH") || c.contains("$BUILD_DIR"));
+    if !uses_build_dir {
+        return exe_path;
+    }
+
+    let dir_path = file_path.parent().unwrap_or_else(|| Path::new(""));
+    // We can unwrap because source files always have a name.
+    build_dir(dir_path).join(exe_path.file_name().unwrap())
+}
+
+/// Fills in the paths of a command that is run for the source file, where
+/// `compile_cmd` is the command the source file is compiled with.
+pub fn prepare_cmd(cmd: &str, file_path: &Path, compile_cmd: Option<&str>) -> Option<Vec<String>> {
     let mut dir_path = file_path.to_path_buf();
     dir_path.pop();
-    let exe_path = file_path.with_extension(EXE_EXTENSION);
+    let build_dir = build_dir(&dir_path);
+    let exe_path = exe_path(file_path, compile_cmd);
     let file_name_no_ext = file_path.file_stem().unwrap().to_str().unwrap();
 
     shlex::split(cmd).map(|args| {
@@ -174,6 +387,7 @@ pub fn prepare_cmd(cmd: &str, file_path: &Path) -> Option<Vec<String>> {
                 arg.replace("$SRC_PATH", &path_to_str(file_path))
                     .replace("$SRC_FILE_NAME_NO_EXT", file_name_no_ext)
                     .replace("$DIR_PATH", &path_to_str(&dir_path))
+                    .replace("$BUILD_DIR", &path_to_str(&build_dir))
                     .replace("$EXE_PATH", &path_to_str(&exe_path))
             })
             .collect()
@@ -0,0 +1,499 @@
+use colored::{ColoredString, Colorize};
+use regex::{Captures, Regex};
+use std::fmt;
+use std::fs;
+
+/// The width of a tab when showing source lines.
+const TAB_WIDTH: usize = 8;
+
+/// The most diagnostics printed with their source lines after a compilation.
+/// Compilers often report many errors that follow from the first one.
+const MAX_PRINTED: usize = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+    Note,
+}
+
+impl Severity {
+    fn from_name(name: &str) -> Self {
+        match name {
+            "warning" => Severity::Warning,
+            "note" => Severity::Note,
+            _ => Severity::Error,
+        }
+    }
+
+    fn colorize(self, text: &str) -> ColoredString {
+        match self {
+            Severity::Error => text.bright_red(),
+            Severity::Warning => text.bright_yellow(),
+            Severity::Note => text.bright_cyan(),
+        }
+    }
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+            Severity::Note => "note",
+        })
+    }
+}
+
+/// A single error, warning or note reported by a compiler.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    /// The file as the compiler named it, which is relative to kitty's working
+    /// directory if it is not absolute.
+    pub file: String,
+    pub line: usize,
+    pub column: Option<usize>,
+    pub severity: Severity,
+    pub message: String,
+}
+
+impl Diagnostic {
+    /// Gives the diagnostic in the format of GCC, which Vim's quickfix list
+    /// and Emacs' compilation mode both understand.
+    pub fn to_quickfix(&self) -> String {
+        match self.column {
+            Some(c) => format!(
+                "{}:{}:{}: {}: {}",
+                self.file, self.line, c, self.severity, self.message
+            ),
+            None => format!(
+                "{}:{}: {}: {}",
+                self.file, self.line, self.severity, self.message
+            ),
+        }
+    }
+
+    /// Prints the diagnostic followed by the line of source code it refers to,
+    /// with the column marked.
+    pub fn print(&self) {
+        println!(
+            "{}: {}",
+            self.severity.colorize(&self.severity.to_string()),
+            self.message.bold()
+        );
+
+        let location = match self.column {
+            Some(c) => format!("{}:{}:{}", self.file, self.line, c),
+            None => format!("{}:{}", self.file, self.line),
+        };
+        println!("  {} {}", "-->".bright_blue(), location);
+
+        let source_line = fs::read_to_string(&self.file)
+            .ok()
+            .and_then(|s| s.lines().nth(self.line.saturating_sub(1)).map(String::from));
+        let source_line = match source_line {
+            Some(l) => l,
+            None => return,
+        };
+
+        let source_line = expand_tabs(&source_line);
+        let number = self.line.to_string();
+        let gutter = " ".repeat(number.len());
+        println!("{} {}", gutter, "|".bright_blue());
+        println!(
+            "{} {} {}",
+            number.bright_blue(),
+            "|".bright_blue(),
+            source_line
+        );
+
+        if let Some(column) = self.column {
+            // Columns past the end of the line point just after it.
+            let padding = " ".repeat(column.saturating_sub(1).min(source_line.chars().count()));
+            println!(
+                "{} {} {}{}",
+                gutter,
+                "|".bright_blue(),
+                padding,
+                self.severity.colorize("^")
+            );
+        }
+    }
+}
+
+/// Finds the diagnostics in the output of a compiler. The formats of GCC and
+/// Clang, rustc, javac, GHC and Go are understood. Lines that are not part of
+/// a diagnostic, and diagnostics without a location, are skipped.
+pub fn parse(output: &str) -> Vec<Diagnostic> {
+    // GCC, Clang and javac, and GHC, which writes its message on the lines
+    // below and may give a range of columns such as 3:7-9.
+    let gcc_re = Regex::new(
+        r"^(?P<file>(?:[A-Za-z]:)?[^:\s][^:]*):(?P<line>\d+):(?:(?P<col>\d+)(?:-\d+)?:)? (?:fatal )?(?P<sev>error|warning|note):? ?(?P<msg>.*)$",
+    )
+    .unwrap();
+    // GHC with -ferror-spans, when the range spans several lines.
+    let ghc_span_re = Regex::new(
+        r"^(?P<file>(?:[A-Za-z]:)?[^:\s][^:]*):\((?P<line>\d+),(?P<col>\d+)\)-\(\d+,\d+\): (?P<sev>error|warning):? ?(?P<msg>.*)$",
+    )
+    .unwrap();
+    // rustc gives the location on the line below the message.
+    let rustc_re =
+        Regex::new(r"^(?P<sev>error|warning)(?:\[(?P<code>[^\]]+)\])?: (?P<msg>.+)$").unwrap();
+    let rustc_location_re =
+        Regex::new(r"^\s*--> (?P<file>.+):(?P<line>\d+):(?P<col>\d+)$").unwrap();
+    // Go has no severities, since it only reports errors.
+    let go_re = Regex::new(
+        r"^(?P<file>(?:[A-Za-z]:)?[^:\s][^:]*\.go):(?P<line>\d+):(?:(?P<col>\d+):)? (?P<msg>.+)$",
+    )
+    .unwrap();
+
+    let lines = output.lines().collect::<Vec<_>>();
+    let mut diagnostics = Vec::new();
+
+    for (i, line) in lines.iter().enumerate() {
+        let caps = match gcc_re.captures(line).or_else(|| ghc_span_re.captures(line)) {
+            Some(c) => c,
+            None => {
+                if let Some(d) = parse_rustc(
+                    line,
+                    lines.get(i + 1).copied(),
+                    &rustc_re,
+                    &rustc_location_re,
+                ) {
+                    diagnostics.push(d);
+                } else if let Some(caps) = go_re.captures(line) {
+                    diagnostics.extend(diagnostic(&caps, Severity::Error, caps["msg"].to_string()));
+                }
+                continue;
+            }
+        };
+
+        let severity = Severity::from_name(&caps["sev"]);
+        let mut message = caps["msg"].trim().to_string();
+
+        // GHC only gives warning flags or error codes such as [GHC-88464] on
+        // the first line, and the message itself on the indented lines below.
+        if message.is_empty() || message.starts_with('[') {
+            let below = lines[i + 1..]
+                .iter()
+                .take_while(|l| l.starts_with(char::is_whitespace))
+                .map(|l| l.trim().trim_start_matches('\u{2022}').trim())
+                .find(|l| !l.is_empty());
+
+            if let Some(text) = below {
+                message = if message.is_empty() {
+                    text.to_string()
+                } else {
+                    format!("{} {}", text, message)
+                };
+            }
+        }
+
+        diagnostics.extend(diagnostic(&caps, severity, message));
+    }
+
+    diagnostics
+}
+
+fn parse_rustc(
+    line: &str,
+    next_line: Option<&str>,
+    rustc_re: &Regex,
+    location_re: &Regex,
+) -> Option<Diagnostic> {
+    let caps = rustc_re.captures(line)?;
+    let location = location_re.captures(next_line?)?;
+
+    let message = match caps.name("code") {
+        Some(code) => format!("{} [{}]", &caps["msg"], code.as_str()),
+        None => caps["msg"].to_string(),
+    };
+
+    diagnostic(&location, Severity::from_name(&caps["sev"]), message)
+}
+
+fn diagnostic(location: &Captures, severity: Severity, message: String) -> Option<Diagnostic> {
+    Some(Diagnostic {
+        file: location["file"].to_string(),
+        line: location["line"].parse().ok()?,
+        column: location.name("col").and_then(|c| c.as_str().parse().ok()),
+        severity,
+        message,
+    })
+}
+
+/// Replaces tabs with spaces up to the next tab stop. Columns are then counted
+/// like GCC counts them, although compilers that count a tab as a single
+/// column are off on lines that are indented with tabs.
+fn expand_tabs(line: &str) -> String {
+    let mut expanded = String::with_capacity(line.len());
+
+    for c in line.chars() {
+        if c == '\t' {
+            let width = TAB_WIDTH - expanded.chars().count() % TAB_WIDTH;
+            expanded.push_str(&" ".repeat(width));
+        } else {
+            expanded.push(c);
+        }
+    }
+
+    expanded
+}
+
+/// Prints diagnostics with their source lines, or in the quickfix format if
+/// `quickfix` is set. Only the first few are shown with source lines.
+pub fn print_all(diagnostics: &[Diagnostic], quickfix: bool) {
+    if quickfix {
+        for d in diagnostics {
+            println!("{}", d.to_quickfix());
+        }
+        return;
+    }
+
+    for d in diagnostics.iter().take(MAX_PRINTED) {
+        d.print();
+        println!();
+    }
+
+    if diagnostics.len() > MAX_PRINTED {
+        println!(
+            "... and {} more messages from the compiler",
+            diagnostics.len() - MAX_PRINTED
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn diagnostic(
+        file: &str,
+        line: usize,
+        column: Option<usize>,
+        severity: Severity,
+        message: &str,
+    ) -> Diagnostic {
+        Diagnostic {
+            file: file.to_string(),
+            line,
+            column,
+            severity,
+            message: message.to_string(),
+        }
+    }
+
+    #[test]
+    fn parses_gcc_and_clang() {
+        let output = "\
+main.cpp: In function 'int main()':
+main.cpp:5:5: error: 'x' was not declared in this scope
+    5 |     x = 1;
+      |     ^
+main.cpp:3:9: warning: unused variable 'y' [-Wunused-variable]
+main.cpp:1:10: fatal error: bits/stdc++.hpp: No such file or directory
+/usr/bin/ld: /tmp/ccXYZ.o: in function `main':
+collect2: error: ld returned 1 exit status
+";
+
+        assert_eq!(
+            parse(output),
+            vec![
+                diagnostic(
+                    "main.cpp",
+                    5,
+                    Some(5),
+                    Severity::Error,
+                    "'x' was not declared in this scope"
+                ),
+                diagnostic(
+                    "main.cpp",
+                    3,
+                    Some(9),
+                    Severity::Warning,
+                    "unused variable 'y' [-Wunused-variable]"
+                ),
+                diagnostic(
+                    "main.cpp",
+                    1,
+                    Some(10),
+                    Severity::Error,
+                    "bits/stdc++.hpp: No such file or directory"
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn parses_rustc() {
+        let output = "\
+error[E0425]: cannot find value `x` in this scope
+ --> main.rs:2:5
+  |
+2 |     x
+  |     ^ not found in this scope
+
+warning: unused variable: `y`
+ --> src/main.rs:3:9
+
+error: aborting due to 1 previous error
+";
+
+        assert_eq!(
+            parse(output),
+            vec![
+                diagnostic(
+                    "main.rs",
+                    2,
+                    Some(5),
+                    Severity::Error,
+                    "cannot find value `x` in this scope [E0425]"
+                ),
+                diagnostic(
+                    "src/main.rs",
+                    3,
+                    Some(9),
+                    Severity::Warning,
+                    "unused variable: `y`"
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn parses_javac() {
+        let output = "\
+Main.java:3: error: cannot find symbol
+        x = 1;
+        ^
+  symbol:   variable x
+  location: class Main
+Main.java:7: warning: [removal] finalize() in Object has been deprecated
+1 error
+";
+
+        assert_eq!(
+            parse(output),
+            vec![
+                diagnostic("Main.java", 3, None, Severity::Error, "cannot find symbol"),
+                diagnostic(
+                    "Main.java",
+                    7,
+                    None,
+                    Severity::Warning,
+                    "[removal] finalize() in Object has been deprecated"
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn parses_ghc_messages_on_the_lines_below() {
+        let output = "\
+Main.hs:3:8: error: [GHC-88464]
+    Variable not in scope: foo :: IO ()
+  |
+3 | main = foo
+  |        ^^^
+
+Main.hs:5:1-4: warning: [-Wmissing-signatures]
+    Top-level binding with no type signature: f :: Int
+
+Main.hs:9:5: error:
+    \u{2022} No instance for (Num String) arising from the literal `1'
+    \u{2022} In the expression: 1
+";
+
+        assert_eq!(
+            parse(output),
+            vec![
+                diagnostic(
+                    "Main.hs",
+                    3,
+                    Some(8),
+                    Severity::Error,
+                    "Variable not in scope: foo :: IO () [GHC-88464]"
+                ),
+                diagnostic(
+                    "Main.hs",
+                    5,
+                    Some(1),
+                    Severity::Warning,
+                    "Top-level binding with no type signature: f :: Int [-Wmissing-signatures]"
+                ),
+                diagnostic(
+                    "Main.hs",
+                    9,
+                    Some(5),
+                    Severity::Error,
+                    "No instance for (Num String) arising from the literal `1'"
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn parses_ghc_spans() {
+        let output = "\
+Main.hs:(3,8)-(4,12): error:
+    \u{2022} Couldn't match expected type `Int' with actual type `[Char]'
+";
+
+        assert_eq!(
+            parse(output),
+            vec![diagnostic(
+                "Main.hs",
+                3,
+                Some(8),
+                Severity::Error,
+                "Couldn't match expected type `Int' with actual type `[Char]'"
+            )]
+        );
+    }
+
+    #[test]
+    fn parses_go() {
+        let output = "\
+# command-line-arguments
+./main.go:5:2: undefined: x
+./main.go:6:2: declared and not used: y
+";
+
+        assert_eq!(
+            parse(output),
+            vec![
+                diagnostic("./main.go", 5, Some(2), Severity::Error, "undefined: x"),
+                diagnostic(
+                    "./main.go",
+                    6,
+                    Some(2),
+                    Severity::Error,
+                    "declared and not used: y"
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn skips_output_without_locations() {
+        assert!(parse("").is_empty());
+        assert!(parse("Traceback (most recent call last):\n  oops\n").is_empty());
+        assert!(parse("error: linker `cc` not found\n").is_empty());
+    }
+
+    #[test]
+    fn gives_quickfix_lines() {
+        let with_column = diagnostic("a.cpp", 5, Some(3), Severity::Error, "oops");
+        let without_column = diagnostic("A.java", 2, None, Severity::Warning, "hm");
+
+        assert_eq!(with_column.to_quickfix(), "a.cpp:5:3: error: oops");
+        assert_eq!(without_column.to_quickfix(), "A.java:2: warning: hm");
+    }
+
+    #[test]
+    fn expands_tabs_to_tab_stops() {
+        assert_eq!(expand_tabs("\tx"), "        x");
+        assert_eq!(expand_tabs("ab\tx"), "ab      x");
+        assert_eq!(expand_tabs("no tabs"), "no tabs");
+    }
+}
@@ -0,0 +1,580 @@
+use crate::compare::{self, Comparison};
+use colored::{ColoredString, Colorize};
+use std::cmp;
+
+/// The number of unchanged lines shown around differences.
+const CONTEXT_LINES: usize = 3;
+
+/// How many lines from the first difference onwards are diffed. Differences
+/// further down are only summarised.
+const DIFF_WINDOW: usize = 500;
+
+/// The maximum number of lines printed in a diff.
+const MAX_DIFF_LINES: usize = 40;
+
+/// Diffing takes time and memory proportional to the product of the number of
+/// lines (or tokens) on each side. Beyond this, only the common beginning and
+/// end are matched up, and everything in between is shown as changed.
+const MAX_DIFF_CELLS: usize = 1_000_000;
+
+/// Lines longer than this are cut off in the unified layout.
+const MAX_LINE_WIDTH: usize = 1000;
+
+/// The width assumed for side-by-side diffs when the terminal width is unknown.
+const DEFAULT_TERMINAL_WIDTH: usize = 120;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Edit {
+    Same(usize, usize),
+    Removed(usize),
+    Added(usize),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Side {
+    Context,
+    Expected,
+    Actual,
+}
+
+/// Prints a diff of the expected answer and the actual output, starting a few
+/// lines before the first line that differs. Lines are compared like the output
+/// is judged, so differences that do not matter are not highlighted, but
+/// trailing whitespace and missing final newlines are always shown.
+pub fn print_diff(expected: &[u8], actual: &[u8], comparison: Comparison, side_by_side: bool) {
+    let (expected_lines, expected_newline) = split_lines(expected);
+    let (actual_lines, actual_newline) = split_lines(actual);
+
+    let lines_match =
+        |i: usize, j: usize| compare::lines_match(expected_lines[i], actual_lines[j], comparison);
+
+    let common = cmp::min(expected_lines.len(), actual_lines.len());
+    let first = (0..common).take_while(|&i| lines_match(i, i)).count();
+
+    if first == expected_lines.len() && first == actual_lines.len() {
+        println!("the output only differs from the answer in whitespace");
+        print_newline_notes(expected_newline, actual_newline);
+        return;
+    }
+
+    println!(
+        "{} (the answer has {} lines, the output has {})",
+        format!("first difference on line {}", first + 1).bold(),
+        expected_lines.len(),
+        actual_lines.len()
+    );
+
+    let start = first.saturating_sub(CONTEXT_LINES);
+    let expected_end = cmp::min(expected_lines.len(), first + DIFF_WINDOW);
+    let actual_end = cmp::min(actual_lines.len(), first + DIFF_WINDOW);
+    let edits = diff(expected_end - start, actual_end - start, |i, j| {
+        lines_match(start + i, start + j)
+    })
+    .into_iter()
+    .map(|edit| match edit {
+        Edit::Same(i, j) => Edit::Same(start + i, start + j),
+        Edit::Removed(i) => Edit::Removed(start + i),
+        Edit::Added(j) => Edit::Added(start + j),
+    })
+    .collect::<Vec<_>>();
+
+    let printer = Printer {
+        expected: &expected_lines,
+        actual: &actual_lines,
+        comparison,
+        column_width: if side_by_side {
+            Some(column_width())
+        } else {
+            None
+        },
+    };
+    let complete = printer.print_edits(&edits);
+
+    if !complete || expected_end < expected_lines.len() || actual_end < actual_lines.len() {
+        let longest = cmp::max(expected_lines.len(), actual_lines.len());
+        let differing = (0..longest)
+            .filter(|&i| i >= common || !lines_match(i, i))
+            .count();
+
+        println!(
+            "{}",
+            format!(
+                "... diff truncated. {} of {} lines differ from the answer line by line",
+                differing, longest
+            )
+            .dimmed()
+        );
+    }
+
+    print_newline_notes(expected_newline, actual_newline);
+}
+
+struct Printer<'a> {
+    expected: &'a [&'a [u8]],
+    actual: &'a [&'a [u8]],
+    comparison: Comparison,
+    /// The width of each column in the side-by-side layout, or `None` for the
+    /// unified layout.
+    column_width: Option<usize>,
+}
+
+impl Printer<'_> {
+    /// Prints the edits with unchanged lines far from any change left out.
+    /// Returns false if the diff had to be cut short.
+    fn print_edits(&self, edits: &[Edit]) -> bool {
+        let is_change = |e: &Edit| !matches!(e, Edit::Same(..));
+        let near_change = |k: usize| {
+            let from = k.saturating_sub(CONTEXT_LINES);
+            let to = cmp::min(edits.len(), k + CONTEXT_LINES + 1);
+            edits[from..to].iter().any(is_change)
+        };
+
+        let mut printed = 0;
+        let mut skipped = false;
+        let mut k = 0;
+
+        while k < edits.len() {
+            if printed >= MAX_DIFF_LINES {
+                return false;
+            }
+
+            match edits[k] {
+                Edit::Same(i, j) => {
+                    if near_change(k) {
+                        if skipped {
+                            println!("{}", "...".dimmed());
+                            skipped = false;
+                        }
+                        self.print_row(Some(i), Some(j), Side::Context, None);
+                        printed += 1;
+                    } else {
+                        skipped = true;
+                    }
+                    k += 1;
+                }
+                _ => {
+                    if skipped {
+                        println!("{}", "...".dimmed());
+                        skipped = false;
+                    }
+
+                    let mut removed = Vec::new();
+                    let mut added = Vec::new();
+                    while let Some(edit) = edits.get(k).filter(|e| is_change(e)) {
+                        match *edit {
+                            Edit::Removed(i) => removed.push(i),
+                            Edit::Added(j) => added.push(j),
+                            Edit::Same(..) => unreachable!(),
+                        }
+                        k += 1;
+                    }
+
+                    match self.print_change(&removed, &added, MAX_DIFF_LINES - printed) {
+                        Some(rows) => printed += rows,
+                        None => return false,
+                    }
+                }
+            }
+        }
+
+        true
+    }
+
+    /// Prints a block of changed lines, pairing up removed and added lines so
+    /// that the tokens that differ between them can be highlighted. Returns the
+    /// number of lines printed, or `None` if the block did not fit in
+    /// `max_lines`.
+    fn print_change(&self, removed: &[usize], added: &[usize], max_lines: usize) -> Option<usize> {
+        let rows = if self.column_width.is_some() {
+            cmp::max(removed.len(), added.len())
+        } else {
+            removed.len() + added.len()
+        };
+
+        if self.column_width.is_some() {
+            for k in 0..cmp::min(rows, max_lines) {
+                self.print_row(
+                    removed.get(k).copied(),
+                    added.get(k).copied(),
+                    Side::Actual,
+                    None,
+                );
+            }
+            return Some(rows).filter(|&r| r <= max_lines);
+        }
+
+        let removed_rows = cmp::min(removed.len(), max_lines);
+        let added_rows = cmp::min(added.len(), max_lines - removed_rows);
+        for (k, &i) in removed.iter().take(removed_rows).enumerate() {
+            self.print_row(Some(i), None, Side::Expected, added.get(k).copied());
+        }
+        for (k, &j) in added.iter().take(added_rows).enumerate() {
+            self.print_row(None, Some(j), Side::Actual, removed.get(k).copied());
+        }
+
+        Some(rows).filter(|&r| r <= max_lines)
+    }
+
+    /// Prints a single row of the diff. In the unified layout, only one side is
+    /// shown, and `paired` is the line on the other side that it is highlighted
+    /// against. In the side-by-side layout, both sides are shown.
+    fn print_row(
+        &self,
+        expected: Option<usize>,
+        actual: Option<usize>,
+        side: Side,
+        paired: Option<usize>,
+    ) {
+        let width = match self.column_width {
+            Some(w) => w,
+            None => {
+                let (sign, number, line, other) = match (side, expected, actual) {
+                    (Side::Actual, _, Some(j)) => {
+                        ("+", j, self.actual[j], paired.map(|i| self.expected[i]))
+                    }
+                    (_, Some(i), _) => (
+                        if side == Side::Context { " " } else { "-" },
+                        i,
+                        self.expected[i],
+                        paired.map(|j| self.actual[j]),
+                    ),
+                    _ => return,
+                };
+
+                let (text, _) = self.render(line, other, side, MAX_LINE_WIDTH);
+                println!(
+                    "{}{} {}",
+                    color(sign, side),
+                    format!("{:>5}", number + 1).dimmed(),
+                    text
+                );
+                return;
+            }
+        };
+
+        let expected_line = expected.map(|i| self.expected[i]);
+        let actual_line = actual.map(|j| self.actual[j]);
+        let (left_side, right_side) = match side {
+            Side::Context => (Side::Context, Side::Context),
+            _ => (Side::Expected, Side::Actual),
+        };
+
+        let (left, left_width) = match expected_line {
+            Some(line) => self.render(line, actual_line, left_side, width),
+            None => (String::new(), 0),
+        };
+        let (right, _) = match actual_line {
+            Some(line) => self.render(line, expected_line, right_side, width),
+            None => (String::new(), 0),
+        };
+
+        let number = |n: Option<usize>| match n {
+            Some(n) => format!("{:>5}", n + 1).dimmed().to_string(),
+            None => " ".repeat(5),
+        };
+        let separator = match side {
+            Side::Context => " ".normal(),
+            _ => "|".yellow(),
+        };
+
+        println!(
+            "{} {}{} {} {} {}",
+            number(expected),
+            left,
+            " ".repeat(width.saturating_sub(left_width)),
+            separator,
+            number(actual),
+            right
+        );
+    }
+
+    /// Renders a line with the tokens that differ from `other` highlighted and
+    /// trailing whitespace made visible. If `other` only differs from the line
+    /// in its whitespace, all whitespace is made visible. The line is cut off
+    /// after `width` characters, but lines that do not fit are started close to
+    /// the first highlighted token. Returns the text along with its width.
+    fn render(
+        &self,
+        line: &[u8],
+        other: Option<&[u8]>,
+        side: Side,
+        width: usize,
+    ) -> (String, usize) {
+        let segments = split_segments(line);
+        let tokens = segments
+            .iter()
+            .filter(|s| s.1)
+            .map(|s| s.0)
+            .collect::<Vec<_>>();
+
+        let mut changed = vec![false; tokens.len()];
+        let mut show_whitespace = false;
+
+        if let (Some(other), true) = (other, side != Side::Context) {
+            let other_tokens = split_segments(other)
+                .into_iter()
+                .filter(|s| s.1)
+                .map(|s| s.0)
+                .collect::<Vec<_>>();
+
+            let token_diff = diff(tokens.len(), other_tokens.len(), |i, j| {
+                let (expected, actual) = match side {
+                    Side::Actual => (other_tokens[j], tokens[i]),
+                    _ => (tokens[i], other_tokens[j]),
+                };
+                compare::token_matches(expected, actual, self.comparison)
+            });
+
+            for edit in token_diff {
+                if let Edit::Removed(i) = edit {
+                    changed[i] = true;
+                }
+            }
+
+            show_whitespace = !changed.contains(&true) && tokens.len() == other_tokens.len();
+        }
+
+        // Long lines start a few tokens before the first difference.
+        let first_changed = changed.iter().position(|&c| c).unwrap_or(0);
+        let mut token_index = 0;
+        let mut skip = first_changed.saturating_sub(3);
+        let mut text = String::new();
+        let mut text_width = 0;
+        let total_width = String::from_utf8_lossy(line).chars().count();
+
+        if total_width > width && skip > 0 {
+            text.push_str(&"…".dimmed().to_string());
+            text_width += 1;
+        } else {
+            skip = 0;
+        }
+
+        let trailing_start = segments.iter().rposition(|s| s.1).map_or(0, |k| k + 1);
+
+        for (k, (bytes, is_token)) in segments.iter().enumerate() {
+            if *is_token {
+                token_index += 1;
+            }
+            if token_index <= skip {
+                continue;
+            }
+
+            let visible_whitespace = !is_token && (show_whitespace || k >= trailing_start);
+            let mut piece = if visible_whitespace {
+                visualise_whitespace(bytes)
+            } else {
+                String::from_utf8_lossy(bytes).replace('\t', " ")
+            };
+
+            // A column is kept free for the ellipsis if more of the line follows.
+            let piece_width = piece.chars().count();
+            let more_follows = k + 1 < segments.len();
+            let cut = text_width + piece_width > width
+                || (more_follows && text_width + piece_width >= width);
+            if cut {
+                piece = piece
+                    .chars()
+                    .take(width.saturating_sub(text_width + 1))
+                    .collect();
+            }
+            text_width += piece.chars().count();
+
+            let colored = if visible_whitespace {
+                piece.yellow()
+            } else if *is_token && changed[token_index - 1] {
+                match side {
+                    Side::Expected => piece.black().on_red(),
+                    _ => piece.black().on_green(),
+                }
+            } else {
+                color(&piece, side)
+            };
+            text.push_str(&colored.to_string());
+
+            if cut {
+                text.push_str(&"…".dimmed().to_string());
+                text_width += 1;
+                break;
+            }
+        }
+
+        (text, text_width)
+    }
+}
+
+fn color(text: &str, side: Side) -> ColoredString {
+    match side {
+        Side::Context => text.normal(),
+        Side::Expected => text.red(),
+        Side::Actual => text.green(),
+    }
+}
+
+fn print_newline_notes(expected_newline: bool, actual_newline: bool) {
+    if expected_newline && !actual_newline {
+        println!("{}", "\\ no newline at end of output".yellow());
+    } else if !expected_newline && actual_newline {
+        println!("{}", "\\ no newline at end of answer".yellow());
+    }
+}
+
+/// Splits the text into lines, keeping any trailing whitespace in each line.
+/// Blank lines at the end are left out, since they are ignored when judging.
+/// Also returns whether the text ended with a newline.
+fn split_lines(text: &[u8]) -> (Vec<&[u8]>, bool) {
+    let ends_with_newline = text.is_empty() || text.ends_with(b"\n");
+
+    let mut lines = text.split(|&b| b == b'\n').collect::<Vec<_>>();
+    while lines
+        .last()
+        .is_some_and(|l| l.iter().all(u8::is_ascii_whitespace))
+    {
+        lines.pop();
+    }
+
+    (lines, ends_with_newline)
+}
+
+/// Splits a line into alternating runs of whitespace and tokens. Each run is
+/// marked with whether it is a token.
+fn split_segments(line: &[u8]) -> Vec<(&[u8], bool)> {
+    let mut segments = Vec::new();
+    let mut start = 0;
+
+    for i in 1..=line.len() {
+        if i == line.len() || line[i].is_ascii_whitespace() != line[start].is_ascii_whitespace() {
+            segments.push((&line[start..i], !line[start].is_ascii_whitespace()));
+            start = i;
+        }
+    }
+
+    segments
+}
+
+fn visualise_whitespace(whitespace: &[u8]) -> String {
+    whitespace
+        .iter()
+        .map(|b| match b {
+            b' ' => '·',
+            b'\t' => '→',
+            b'\r' => '␍',
+            _ => '␣',
+        })
+        .collect()
+}
+
+/// The width of each side in the side-by-side layout, which fits two columns
+/// with line numbers in the terminal.
+fn column_width() -> usize {
+    let width = std::env::var("COLUMNS")
+        .ok()
+        .and_then(|c| c.parse().ok())
+        .or_else(terminal_width)
+        .unwrap_or(DEFAULT_TERMINAL_WIDTH);
+
+    cmp::max(width.saturating_sub(15) / 2, 10)
+}
+
+#[cfg(unix)]
+fn terminal_width() -> Option<usize> {
+    let mut size = libc::winsize {
+        ws_row: 0,
+        ws_col: 0,
+        ws_xpixel: 0,
+        ws_ypixel: 0,
+    };
+
+    match unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) } {
+        0 if size.ws_col > 0 => Some(size.ws_col as usize),
+        _ => None,
+    }
+}
+
+#[cfg(not(unix))]
+fn terminal_width() -> Option<usize> {
+    None
+}
+
+/// Finds the edits that turn a sequence of length `a_len` into one of length
+/// `b_len` by computing their longest common subsequence. Removals come before
+/// additions in each changed block.
+fn diff<F: Fn(usize, usize) -> bool>(a_len: usize, b_len: usize, eq: F) -> Vec<Edit> {
+    let common = cmp::min(a_len, b_len);
+    let prefix = (0..common).take_while(|&i| eq(i, i)).count();
+    let suffix = (0..common - prefix)
+        .take_while(|&k| eq(a_len - 1 - k, b_len - 1 - k))
+        .count();
+
+    let n = a_len - prefix - suffix;
+    let m = b_len - prefix - suffix;
+    let mut edits = (0..prefix).map(|i| Edit::Same(i, i)).collect::<Vec<_>>();
+
+    let (mut i, mut j) = (0, 0);
+    if n * m <= MAX_DIFF_CELLS {
+        // lengths[i * (m + 1) + j] is the length of the longest common
+        // subsequence of what remains after skipping i and j elements.
+        let mut lengths = vec![0u32; (n + 1) * (m + 1)];
+        for i in (0..n).rev() {
+            for j in (0..m).rev() {
+                lengths[i * (m + 1) + j] = if eq(prefix + i, prefix + j) {
+                    lengths[(i + 1) * (m + 1) + j + 1] + 1
+                } else {
+                    cmp::max(lengths[(i + 1) * (m + 1) + j], lengths[i * (m + 1) + j + 1])
+                };
+            }
+        }
+
+        while i < n && j < m {
+            if eq(prefix + i, prefix + j) {
+                edits.push(Edit::Same(prefix + i, prefix + j));
+                i += 1;
+                j += 1;
+            } else if lengths[(i + 1) * (m + 1) + j] >= lengths[i * (m + 1) + j + 1] {
+                edits.push(Edit::Removed(prefix + i));
+                i += 1;
+            } else {
+                edits.push(Edit::Added(prefix + j));
+                j += 1;
+            }
+        }
+    }
+
+    edits.extend((i..n).map(|i| Edit::Removed(prefix + i)));
+    edits.extend((j..m).map(|j| Edit::Added(prefix + j)));
+    edits.extend((0..suffix).map(|k| Edit::Same(a_len - suffix + k, b_len - suffix + k)));
+
+    edits
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render_width(line: &[u8], width: usize) -> usize {
+        let printer = Printer {
+            expected: &[],
+            actual: &[],
+            comparison: Comparison::default(),
+            column_width: Some(width),
+        };
+        printer.render(line, None, Side::Context, width).1
+    }
+
+    #[test]
+    fn render_fits_lines_that_are_short_enough() {
+        assert_eq!(render_width(b"ab cd", 5), 5);
+        assert_eq!(render_width(b"ab cd", 10), 5);
+    }
+
+    #[test]
+    fn render_keeps_cut_lines_within_width() {
+        // The first token exactly fills the column.
+        assert_eq!(render_width(b"abcde fgh", 5), 5);
+        assert_eq!(render_width(b"abcde fgh", 6), 6);
+        assert_eq!(render_width(b"abcdefgh", 5), 5);
+
+        for width in 1..12 {
+            assert!(render_width(b"1 22 333 4444 55555", width) <= width);
+        }
+    }
+}
@@ -0,0 +1,50 @@
+use crate::StdErr;
+use std::error::Error;
+use std::fmt;
+
+// The exit codes of kitty besides 0, which means that everything went well.
+// Scripts depend on these, so they must not change. They are documented in the
+// README.
+
+/// Kitty itself failed, e.g. because of invalid arguments or network problems.
+pub const ERROR: i32 = 1;
+/// At least one local test case failed.
+pub const TESTS_FAILED: i32 = 2;
+/// The solution did not compile, locally or on Kattis.
+pub const COMPILE_ERROR: i32 = 3;
+/// Kattis rejected the submission.
+pub const REJECTED: i32 = 4;
+
+/// An outcome that makes kitty exit with a specific code. Failures that have
+/// already been explained to the user carry no message.
+#[derive(Debug)]
+pub struct Failure {
+    code: i32,
+    message: String,
+}
+
+impl Failure {
+    pub fn with_message(code: i32, message: impl Into<String>) -> StdErr {
+        Box::new(Self {
+            code,
+            message: message.into(),
+        })
+    }
+
+    pub fn silent(code: i32) -> StdErr {
+        Self::with_message(code, String::new())
+    }
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.write_str(&self.message)
+    }
+}
+
+impl Error for Failure {}
+
+/// The exit code for an error returned by a command.
+pub fn of(err: &StdErr) -> i32 {
+    err.downcast_ref::<Failure>().map_or(ERROR, |f| f.code)
+}
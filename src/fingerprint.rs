@@ -0,0 +1,110 @@
+use crate::config;
+use crate::lang::Language;
+use crate::test_layout::TestLayout;
+use crate::watch;
+use crate::StdErr;
+use std::collections::hash_map::DefaultHasher;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+
+/// The name of the directory in the build directory that holds the
+/// fingerprints of the last successful compilations, one file per source file.
+/// Keeping them with the build output means that they are removed along with
+/// it.
+const FINGERPRINTS_DIR_NAME: &str = "fingerprints";
+
+/// Identifies a compilation by the contents of the source file and of the other
+/// files next to it that it may include, the compile command as it is run and
+/// the language. If the fingerprint is the same as the last time the source
+/// file compiled successfully, compiling it again would give the same program.
+#[derive(Debug)]
+pub struct Fingerprint {
+    value: String,
+    /// Where the fingerprint is saved.
+    path: PathBuf,
+    /// The compiled program, if the language writes it to `$EXE_PATH`.
+    executable: Option<PathBuf>,
+}
+
+impl Fingerprint {
+    /// Creates the fingerprint of compiling `source`. Changes to the files in
+    /// `ignored`, which are not read by the compiler, do not count.
+    pub fn new(
+        source: &Path,
+        lang: &Language,
+        compile_cmd: &[String],
+        ignored: &[PathBuf],
+    ) -> Result<Self, StdErr> {
+        let contents = match fs::read(source) {
+            Ok(c) => c,
+            Err(_) => return Err(format!("failed to read {}", source.display()).into()),
+        };
+
+        // Only the hash of the contents is kept, not a copy of them.
+        let mut hasher = DefaultHasher::new();
+        lang.to_string().hash(&mut hasher);
+        compile_cmd.hash(&mut hasher);
+        contents.hash(&mut hasher);
+
+        // We can unwrap because the source file was just read.
+        let file_name = source.file_name().unwrap();
+        let build_dir = config::build_dir(source.parent().unwrap_or_else(|| Path::new("")));
+
+        // Headers and other source files may be included, and since there is
+        // no telling which, every file that watch mode runs again for counts.
+        // Test cases are left out, as they never change the program.
+        let dir = match source.parent() {
+            Some(d) if d != Path::new("") => d,
+            _ => Path::new("."),
+        };
+        let layout = TestLayout::for_problem(dir)?;
+        let dir = &dir.canonicalize().unwrap_or_else(|_| dir.to_path_buf());
+        for path in watch::watched_files(dir, ignored) {
+            if layout.is_test_file(&path, dir) {
+                continue;
+            }
+
+            if let Ok(contents) = fs::read(&path) {
+                path.strip_prefix(dir).unwrap_or(&path).hash(&mut hasher);
+                contents.hash(&mut hasher);
+            }
+        }
+
+        Ok(Self {
+            value: format!("{:016x}", hasher.finish()),
+            path: build_dir.join(FINGERPRINTS_DIR_NAME).join(file_name),
+            executable: if lang.compiles_to_executable() {
+                Some(lang.exe_path(source))
+            } else {
+                None
+            },
+        })
+    }
+
+    /// Whether the program was compiled from the same source with the same
+    /// command before, and is still there.
+    pub fn is_up_to_date(&self) -> bool {
+        let saved = match fs::read_to_string(&self.path) {
+            Ok(s) => s,
+            Err(_) => return false,
+        };
+
+        saved.trim() == self.value && self.executable.as_ref().is_none_or(|e| e.exists())
+    }
+
+    /// Remembers the fingerprint after a successful compilation.
+    pub fn save(&self) -> Result<(), StdErr> {
+        // We can unwrap because the path is always inside the build directory.
+        let dir = self.path.parent().unwrap();
+
+        if fs::create_dir_all(dir)
+            .and_then(|_| fs::write(&self.path, &self.value))
+            .is_err()
+        {
+            return Err(format!("failed to write {}", self.path.display()).into());
+        }
+
+        Ok(())
+    }
+}
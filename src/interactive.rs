@@ -0,0 +1,349 @@
+use crate::process::{self, Isolation, ResourceLimits, Usage, UNLIMITED};
+use crate::validator::{self, Validator, EXIT_CODE_ACCEPTED};
+use crate::verdict::Verdict;
+use crate::StdErr;
+use std::io::{self, Read, Write};
+use std::path::Path;
+use std::process::{Child, ExitStatus, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// How long the interactor may keep running after the time limit of the
+/// solution has passed.
+const INTERACTOR_GRACE_PERIOD: Duration = Duration::from_secs(10);
+
+/// How much of the exchange between the programs is kept in the transcript.
+const TRANSCRIPT_LIMIT: usize = 1024 * 1024;
+
+/// The result of running a solution against an interactor.
+pub struct Interaction {
+    /// How the solution ran. Its stdout is empty, since everything it wrote was
+    /// sent to the interactor and can be found in the transcript instead.
+    pub output: process::Output,
+    /// The verdict of the interactor along with its judge message, if any.
+    pub judgement: (Verdict, Option<String>),
+    /// The exchange between the programs in the format of Kattis' `.interaction`
+    /// files, where lines starting with `>` were sent to the solution and lines
+    /// starting with `<` were sent by the solution.
+    pub transcript: String,
+}
+
+/// Runs the solution with its stdin and stdout connected to the interactor,
+/// which is run like Kattis runs interactive output validators:
+///
+/// ```text
+/// interactor input_file judge_answer feedback_dir [flags] <> solution
+/// ```
+///
+/// The solution is stopped once its time limit has passed, once it exceeds
+/// the output limit, or as soon as the interactor has rejected it.
+pub fn run(
+    solution_cmd: &[String],
+    interactor: &Validator,
+    test_in: &Path,
+    test_ans: &Path,
+    time_limit: Duration,
+    limits: &ResourceLimits,
+    isolation: &Isolation,
+) -> Result<Interaction, StdErr> {
+    let feedback_dir = tempfile::tempdir()?;
+    let interactor_cmd = interactor.command(test_in, test_ans, feedback_dir.path());
+
+    let start_time = Instant::now();
+    let mut solution = process::spawn(
+        solution_cmd,
+        Stdio::piped(),
+        Stdio::piped(),
+        limits,
+        isolation,
+    )?;
+    let mut judge = match process::spawn(
+        &interactor_cmd,
+        Stdio::piped(),
+        Stdio::piped(),
+        &ResourceLimits::default(),
+        &Isolation::default(),
+    ) {
+        Ok(j) => j,
+        Err(e) => {
+            stop(&mut solution);
+            return Err(e);
+        }
+    };
+
+    let transcript = Arc::new(Mutex::new(Transcript::default()));
+    let output_limit_exceeded = Arc::new(AtomicBool::new(false));
+
+    let relays = match (
+        solution.stdout.take(),
+        solution.stdin.take(),
+        judge.stdout.take(),
+        judge.stdin.take(),
+    ) {
+        (Some(solution_out), Some(solution_in), Some(judge_out), Some(judge_in)) => vec![
+            relay(
+                solution_out,
+                judge_in,
+                Direction::FromSolution,
+                limits.output.unwrap_or(UNLIMITED),
+                Some(Arc::clone(&output_limit_exceeded)),
+                Arc::clone(&transcript),
+            ),
+            relay(
+                judge_out,
+                solution_in,
+                Direction::ToSolution,
+                UNLIMITED,
+                None,
+                Arc::clone(&transcript),
+            ),
+        ],
+        _ => {
+            stop(&mut solution);
+            stop(&mut judge);
+            return Err("failed to connect the solution to the interactor".into());
+        }
+    };
+
+    let solution_stderr = solution
+        .stderr
+        .take()
+        .map(|pipe| process::read_in_background(pipe, process::STDERR_CAPTURE_LIMIT, None));
+    let judge_stderr = judge
+        .stderr
+        .take()
+        .map(|pipe| process::read_in_background(pipe, process::STDERR_CAPTURE_LIMIT, None));
+
+    let result = wait_for_both(
+        &mut solution,
+        &mut judge,
+        start_time,
+        time_limit,
+        &output_limit_exceeded,
+    );
+
+    // Either program may have left processes behind that keep the pipes open.
+    process::kill_tree(&mut solution);
+    process::kill_tree(&mut judge);
+
+    let (solution_exit, judge_status) = match result {
+        Ok(r) => r,
+        Err(_) => {
+            let _ = process::wait(&mut solution);
+            let _ = process::wait(&mut judge);
+            return Err("failed to wait for program to exit".into());
+        }
+    };
+
+    for relay in relays {
+        if relay.join().is_err() {
+            return Err("failed to pass data between the solution and the interactor".into());
+        }
+    }
+
+    let output = process::Output {
+        status: solution_exit.status,
+        stdout: Vec::new(),
+        stderr: process::join_reader(solution_stderr)?,
+        output_limit_exceeded: output_limit_exceeded.load(Ordering::SeqCst),
+        elapsed: solution_exit.elapsed,
+        usage: solution_exit.usage,
+    };
+    let judge_stderr = process::join_reader(judge_stderr)?;
+    let judgement = validator::judgement(judge_status, &judge_stderr, feedback_dir.path());
+
+    let transcript = match transcript.lock() {
+        Ok(t) => t.render(),
+        Err(_) => return Err("failed to record the transcript".into()),
+    };
+
+    Ok(Interaction {
+        output,
+        judgement,
+        transcript,
+    })
+}
+
+/// How the solution exited.
+#[derive(Clone, Copy)]
+struct SolutionExit {
+    /// The exit status, or `None` if the solution timed out.
+    status: Option<ExitStatus>,
+    elapsed: Duration,
+    usage: Option<Usage>,
+}
+
+/// Waits until both programs have exited, killing them if they run for too
+/// long after `start_time`. Along with how the solution exited, the exit status
+/// of the interactor is returned, which is `None` if it had to be killed.
+fn wait_for_both(
+    solution: &mut Child,
+    judge: &mut Child,
+    start_time: Instant,
+    time_limit: Duration,
+    output_limit_exceeded: &AtomicBool,
+) -> io::Result<(SolutionExit, Option<ExitStatus>)> {
+    let deadline = start_time + time_limit;
+    let mut solution_exit = None;
+    let mut judge_exit: Option<Option<ExitStatus>> = None;
+
+    loop {
+        if solution_exit.is_none() {
+            // There is no reason to let the solution keep running once the
+            // interactor has made up its mind.
+            let judge_rejected = match judge_exit {
+                Some(Some(status)) => status.code() != Some(EXIT_CODE_ACCEPTED),
+                Some(None) => true,
+                None => false,
+            };
+
+            let exited = if let Some((status, usage)) = process::try_wait(solution)? {
+                Some((Some(status), usage))
+            } else if judge_rejected || output_limit_exceeded.load(Ordering::SeqCst) {
+                process::kill_tree(solution);
+                let (status, usage) = process::wait(solution)?;
+                Some((Some(status), usage))
+            } else if Instant::now() >= deadline {
+                process::kill_tree(solution);
+                let (_, usage) = process::wait(solution)?;
+                Some((None, usage))
+            } else {
+                None
+            };
+
+            solution_exit = exited.map(|(status, usage)| SolutionExit {
+                status,
+                elapsed: start_time.elapsed(),
+                usage,
+            });
+        }
+
+        if judge_exit.is_none() {
+            if let Some((status, _)) = process::try_wait(judge)? {
+                judge_exit = Some(Some(status));
+            } else if Instant::now() >= deadline + INTERACTOR_GRACE_PERIOD {
+                process::kill_tree(judge);
+                process::wait(judge)?;
+                judge_exit = Some(None);
+            }
+        }
+
+        if let (Some(solution_exit), Some(judge_status)) = (solution_exit, judge_exit) {
+            return Ok((solution_exit, judge_status));
+        }
+
+        thread::sleep(process::POLL_INTERVAL);
+    }
+}
+
+fn stop(child: &mut Child) {
+    process::kill_tree(child);
+    let _ = process::wait(child);
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Direction {
+    ToSolution,
+    FromSolution,
+}
+
+#[derive(Default)]
+struct Transcript {
+    chunks: Vec<(Direction, Vec<u8>)>,
+    size: usize,
+    truncated: bool,
+}
+
+impl Transcript {
+    fn record(&mut self, direction: Direction, data: &[u8]) {
+        if self.size + data.len() > TRANSCRIPT_LIMIT {
+            self.truncated = true;
+            return;
+        }
+        self.size += data.len();
+
+        match self.chunks.last_mut() {
+            Some((d, chunk)) if *d == direction => chunk.extend_from_slice(data),
+            _ => self.chunks.push((direction, data.to_vec())),
+        }
+    }
+
+    fn render(&self) -> String {
+        let mut text = String::new();
+
+        for (direction, chunk) in &self.chunks {
+            let prefix = match direction {
+                Direction::ToSolution => '>',
+                Direction::FromSolution => '<',
+            };
+
+            for line in String::from_utf8_lossy(chunk).lines() {
+                text.push(prefix);
+                text.push_str(line);
+                text.push('\n');
+            }
+        }
+
+        if self.truncated {
+            text.push_str("... (transcript truncated)\n");
+        }
+
+        text
+    }
+}
+
+/// Copies everything from one program to the other on a separate thread while
+/// recording it in the transcript. Once more than `limit` bytes have been
+/// read, `exceeded` is set, and the rest is discarded. When the source closes
+/// its end, so does the destination, such that the other program sees the end
+/// of its input.
+fn relay<R, W>(
+    mut from: R,
+    to: W,
+    direction: Direction,
+    limit: u64,
+    exceeded: Option<Arc<AtomicBool>>,
+    transcript: Arc<Mutex<Transcript>>,
+) -> JoinHandle<()>
+where
+    R: Read + Send + 'static,
+    W: Write + Send + 'static,
+{
+    thread::spawn(move || {
+        let mut to = Some(to);
+        let mut buf = [0; 8192];
+        let mut total = 0;
+
+        loop {
+            let n = match from.read(&mut buf) {
+                Ok(0) => break,
+                Ok(n) => n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(_) => break,
+            };
+
+            total += n as u64;
+            if total > limit {
+                if let Some(flag) = &exceeded {
+                    flag.store(true, Ordering::SeqCst);
+                }
+                to = None;
+                continue;
+            }
+
+            if let Ok(mut t) = transcript.lock() {
+                t.record(direction, &buf[..n]);
+            }
+
+            // If the other program has stopped reading, the data is dropped but
+            // the source is still drained so that it is never blocked.
+            if let Some(w) = &mut to {
+                if w.write_all(&buf[..n]).and_then(|_| w.flush()).is_err() {
+                    to = None;
+                }
+            }
+        }
+    })
+}
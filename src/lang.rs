@@ -1,13 +1,32 @@
 use crate::{config, StdErr};
 use std::fmt;
-use std::path::Path;
+use std::fs;
+use std::path::{Path, PathBuf};
 
-#[derive(Debug)]
+/// A named variant of a language's commands, such as a debug build. Commands
+/// that are left out are those of the language.
+#[derive(Debug, Clone)]
+pub struct Profile {
+    pub name: String,
+    pub run_cmd: Option<String>,
+    pub compile_cmd: Option<String>,
+    /// The memory limit in bytes to run the program with instead of that of
+    /// the problem, for programs built with sanitizers that reserve a lot of
+    /// memory up front.
+    pub memory_limit: Option<u64>,
+}
+
+#[derive(Debug, Clone)]
 pub struct Language {
     name: String,
     file_ext: String,
     run_cmd: String,
     compile_cmd: Option<String>,
+    profiles: Vec<Profile>,
+    /// The profile used when none is chosen, if any.
+    default_profile: Option<String>,
+    /// The memory limit of the chosen profile, if it has one.
+    memory_limit: Option<u64>,
 }
 
 impl Language {
@@ -16,6 +35,8 @@ impl Language {
         file_ext: String,
         run_cmd: String,
         compile_cmd: Option<String>,
+        profiles: Vec<Profile>,
+        default_profile: Option<String>,
     ) -> Self {
         // TODO: Add some sort of input validation (ex. valid file extension, strip whitespace etc.)
         Language {
@@ -23,6 +44,9 @@ impl Language {
             file_ext,
             run_cmd,
             compile_cmd,
+            profiles,
+            default_profile,
+            memory_limit: None,
         }
     }
 
@@ -30,8 +54,77 @@ impl Language {
         &self.file_ext
     }
 
+    pub fn profiles(&self) -> &[Profile] {
+        &self.profiles
+    }
+
+    pub fn default_profile(&self) -> Option<&str> {
+        self.default_profile.as_deref()
+    }
+
+    /// The memory limit in bytes set by the chosen profile, which takes the
+    /// place of that of the problem.
+    pub fn memory_limit(&self) -> Option<u64> {
+        self.memory_limit
+    }
+
+    /// Gives the language with the commands and limits of the named profile, or
+    /// of the default profile if no name is given. Without either, the language is
+    /// given as it is.
+    pub fn with_profile(&self, name: Option<&str>) -> Result<Language, StdErr> {
+        let name = match name.or_else(|| self.default_profile()) {
+            Some(n) => n,
+            None => return Ok(self.clone()),
+        };
+
+        let profile = match self.profiles.iter().find(|p| p.name == name) {
+            Some(p) => p,
+            None if self.profiles.is_empty() => {
+                return Err(format!("{} has no profiles in the config file", self.name).into())
+            }
+            None => {
+                let names = self
+                    .profiles
+                    .iter()
+                    .map(|p| p.name.as_str())
+                    .collect::<Vec<_>>();
+
+                return Err(format!(
+                    "{} has no profile named \"{}\". its profiles are: {}",
+                    self.name,
+                    name,
+                    names.join(", ")
+                )
+                .into());
+            }
+        };
+
+        Ok(Language {
+            run_cmd: profile
+                .run_cmd
+                .clone()
+                .unwrap_or_else(|| self.run_cmd.clone()),
+            compile_cmd: profile
+                .compile_cmd
+                .clone()
+                .or_else(|| self.compile_cmd.clone()),
+            memory_limit: profile.memory_limit,
+            ..self.clone()
+        })
+    }
+
+    /// Whether the program that is run is the one compiled to `$EXE_PATH`.
+    pub fn compiles_to_executable(&self) -> bool {
+        self.compile_cmd.is_some() && self.run_cmd.contains("$EXE_PATH")
+    }
+
+    /// Where the program compiled from `file_path` is written.
+    pub fn exe_path(&self, file_path: &Path) -> PathBuf {
+        config::exe_path(file_path, self.compile_cmd.as_deref())
+    }
+
     pub fn get_run_cmd(&self, file_path: &Path) -> Result<Vec<String>, StdErr> {
-        config::prepare_cmd(&self.run_cmd, file_path)
+        config::prepare_cmd(&self.run_cmd, file_path, self.compile_cmd.as_deref())
             .ok_or_else(|| "failed to parse run command".into())
     }
 
@@ -41,10 +134,19 @@ impl Language {
             None => return Ok(None),
         };
 
-        match config::prepare_cmd(cmd_str, file_path) {
-            Some(c) => Ok(Some(c)),
-            None => Err("failed to parse compile command".into()),
+        let cmd = match config::prepare_cmd(cmd_str, file_path, Some(cmd_str)) {
+            Some(c) => c,
+            None => return Err("failed to parse compile command".into()),
+        };
+
+        // Compilers expect the directory they write to to exist already.
+        let dir_path = file_path.parent().unwrap_or_else(|| Path::new(""));
+        let build_dir = config::build_dir(dir_path);
+        if fs::create_dir_all(&build_dir).is_err() {
+            return Err(format!("failed to create {}", build_dir.display()).into());
         }
+
+        Ok(Some(cmd))
     }
 }
 
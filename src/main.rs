@@ -4,11 +4,26 @@ use lazy_static::lazy_static;
 
 mod cli;
 mod commands;
+mod compare;
 mod config;
+mod diagnostics;
+mod diff;
+mod exit_code;
+mod fingerprint;
+mod interactive;
 mod kattis_client;
 mod lang;
+mod metadata;
 mod problem;
+mod process;
+mod report;
+#[cfg(target_os = "linux")]
+mod sandbox;
+mod test_layout;
 mod utils;
+mod validator;
+mod verdict;
+mod watch;
 
 type StdErr = Box<dyn std::error::Error>;
 
@@ -27,6 +42,8 @@ async fn main() {
     let matches = app.get_matches();
     let command_result = match matches.subcommand() {
         ("test", Some(sub)) => commands::test(sub).await,
+        ("tests", Some(sub)) => commands::tests(sub).await,
+        ("clean", Some(sub)) => commands::clean(sub).await,
         ("get", Some(sub)) => commands::get(sub).await,
         ("submit", Some(sub)) => commands::submit(sub).await,
         ("history", Some(sub)) => commands::history(sub).await,
@@ -43,7 +60,10 @@ async fn main() {
 
 fn exit_if_err<T>(res: &Result<T, StdErr>) {
     if let Err(e) = res {
-        eprintln!("{}: {}", "error".bright_red(), e);
-        std::process::exit(1);
+        if !e.to_string().is_empty() {
+            eprintln!("{}: {}", "error".bright_red(), e);
+        }
+
+        std::process::exit(exit_code::of(e));
     }
 }
@@ -0,0 +1,174 @@
+use crate::test_layout::LayoutSettings;
+use crate::StdErr;
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+use yaml_rust::yaml::Hash;
+use yaml_rust::{Yaml, YamlEmitter, YamlLoader};
+
+/// The name of the file in a problem directory that contains the problem's
+/// metadata. It uses the same layout as `problem.yaml` in Kattis' problem
+/// packages, so limits are stored under the `limits` key.
+pub const METADATA_FILE_NAME: &str = "problem.yaml";
+
+/// Information about how a problem is judged, such as its time limit. Every
+/// field is optional since kitty may not have been able to find it on Kattis.
+#[derive(Debug, Default)]
+pub struct Metadata {
+    /// The CPU time limit given on the problem page.
+    pub time_limit: Option<Duration>,
+    /// The memory limit in megabytes.
+    pub memory_limit: Option<u64>,
+    /// The maximum size of the output in megabytes.
+    pub output_limit: Option<u64>,
+    /// The stack size limit in megabytes. This is not given by Kattis, but can
+    /// be added manually.
+    pub stack_limit: Option<u64>,
+    /// The maximum number of processes. This is not given by Kattis, but can be
+    /// added manually.
+    pub process_limit: Option<u64>,
+    /// The maximum size in megabytes of files written by the program. This is
+    /// not given by Kattis, but can be added manually.
+    pub file_size_limit: Option<u64>,
+    /// Flags for comparing output with the answer, written as in Kattis'
+    /// problem packages, e.g. `validator_flags: float_tolerance 1e-6`.
+    pub validator_flags: Vec<String>,
+    /// A custom output validator. This is either a path relative to the problem
+    /// directory or a shell command.
+    pub validator: Option<String>,
+    /// The interactor of an interactive problem, given like the validator.
+    pub interactor: Option<String>,
+    /// Where the test cases are found, if not where `kitty.yml` says.
+    pub test_layout: LayoutSettings,
+}
+
+impl Metadata {
+    /// Reads the metadata file in the given problem directory. If the file does
+    /// not exist, empty metadata is returned.
+    pub fn load(problem_dir: &Path) -> Result<Self, StdErr> {
+        let path = problem_dir.join(METADATA_FILE_NAME);
+
+        if !path.exists() {
+            return Ok(Default::default());
+        }
+
+        let text = match fs::read_to_string(&path) {
+            Ok(t) => t,
+            Err(_) => return Err(format!("failed to read {}", METADATA_FILE_NAME).into()),
+        };
+
+        Self::from_yaml(&text)
+    }
+
+    pub fn from_yaml(yaml_str: &str) -> Result<Self, StdErr> {
+        let docs = match YamlLoader::load_from_str(yaml_str) {
+            Ok(d) => d,
+            Err(e) => return Err(format!("failed to parse {}: {}", METADATA_FILE_NAME, e).into()),
+        };
+
+        let doc = match docs.first() {
+            Some(d) => d,
+            None => return Ok(Default::default()),
+        };
+
+        let limits = &doc["limits"];
+        let time_limit = as_f64(&limits["time_limit"])
+            .filter(|s| s.is_finite() && *s > 0.0)
+            .map(Duration::from_secs_f64);
+
+        Ok(Self {
+            time_limit,
+            memory_limit: as_u64(&limits["memory"]),
+            output_limit: as_u64(&limits["output"]),
+            stack_limit: as_u64(&limits["stack"]),
+            process_limit: as_u64(&limits["processes"]),
+            file_size_limit: as_u64(&limits["file_size"]),
+            validator_flags: doc["validator_flags"]
+                .as_str()
+                .map(|f| f.split_whitespace().map(str::to_string).collect())
+                .unwrap_or_default(),
+            validator: doc["validator"].as_str().map(str::to_string),
+            interactor: doc["interactor"].as_str().map(str::to_string),
+            test_layout: LayoutSettings::from_yaml(&doc["tests"], METADATA_FILE_NAME)?,
+        })
+    }
+
+    /// Writes the metadata file to the given problem directory, replacing any
+    /// existing one.
+    pub fn save(&self, problem_dir: &Path) -> Result<(), StdErr> {
+        let mut limits = Hash::new();
+
+        if let Some(t) = self.time_limit {
+            limits.insert(
+                Yaml::String("time_limit".to_string()),
+                Yaml::Real(t.as_secs_f64().to_string()),
+            );
+        }
+
+        let integer_limits = [
+            ("memory", self.memory_limit),
+            ("output", self.output_limit),
+            ("stack", self.stack_limit),
+            ("processes", self.process_limit),
+            ("file_size", self.file_size_limit),
+        ];
+
+        for (key, value) in integer_limits.iter() {
+            if let Some(v) = value {
+                limits.insert(Yaml::String(key.to_string()), Yaml::Integer(*v as i64));
+            }
+        }
+
+        let mut doc = Hash::new();
+        doc.insert(Yaml::String("limits".to_string()), Yaml::Hash(limits));
+
+        if !self.validator_flags.is_empty() {
+            doc.insert(
+                Yaml::String("validator_flags".to_string()),
+                Yaml::String(self.validator_flags.join(" ")),
+            );
+        }
+
+        if let Some(v) = &self.validator {
+            doc.insert(
+                Yaml::String("validator".to_string()),
+                Yaml::String(v.clone()),
+            );
+        }
+
+        if let Some(i) = &self.interactor {
+            doc.insert(
+                Yaml::String("interactor".to_string()),
+                Yaml::String(i.clone()),
+            );
+        }
+
+        if let Some(t) = self.test_layout.to_yaml() {
+            doc.insert(Yaml::String("tests".to_string()), t);
+        }
+
+        let mut out = String::new();
+        if YamlEmitter::new(&mut out).dump(&Yaml::Hash(doc)).is_err() {
+            return Err(format!("failed to serialise {}", METADATA_FILE_NAME).into());
+        }
+        out.push('\n');
+
+        if fs::write(problem_dir.join(METADATA_FILE_NAME), out).is_err() {
+            return Err(format!("failed to write {}", METADATA_FILE_NAME).into());
+        }
+
+        Ok(())
+    }
+}
+
+fn as_f64(value: &Yaml) -> Option<f64> {
+    match value {
+        Yaml::Integer(i) => Some(*i as f64),
+        Yaml::Real(_) => value.as_f64(),
+        _ => None,
+    }
+}
+
+fn as_u64(value: &Yaml) -> Option<u64> {
+    value.as_i64().filter(|i| *i >= 0).map(|i| i as u64)
+}
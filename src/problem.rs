@@ -1,17 +1,44 @@
 use crate::lang::Language;
+use crate::metadata::Metadata;
+use crate::test_layout::TestLayout;
+use crate::utils::natural_cmp;
 use crate::StdErr;
 use crate::CFG as cfg;
 use clap::ArgMatches;
 use regex::Regex;
-use std::collections::HashMap;
 use std::env;
 use std::io;
 use std::path::{Path, PathBuf};
 
+/// The name of the directory in which kitty keeps what it needs to remember
+/// about a problem between runs.
+pub const STATE_DIR_NAME: &str = ".kitty";
+
 fn path_str(p: &Path) -> &str {
     p.to_str().expect("path did not contain valid unicode")
 }
 
+/// The name of the subfolder of a problem that holds its test cases.
+pub const TEST_DIR_NAME: &str = "test";
+
+/// The name of the subfolder of the test folder that holds the custom tests,
+/// which are kept apart so that fetching the samples never touches them.
+pub const CUSTOM_TEST_DIR_NAME: &str = "custom";
+
+/// The files of a single test case.
+#[derive(Debug, Clone)]
+pub struct TestCase {
+    /// The name of the input file without its extension, prefixed with
+    /// "custom/" for custom tests.
+    pub name: String,
+    pub input: PathBuf,
+    /// The expected output, or `None` if the input has no answer file. Such
+    /// test cases are still run, but their output is not checked.
+    pub answer: Option<PathBuf>,
+    /// Whether this is a custom test rather than an official sample.
+    pub custom: bool,
+}
+
 #[derive(Debug)]
 pub struct Problem<'a> {
     name: String,
@@ -99,12 +126,6 @@ impl<'a> Problem<'a> {
         Ok(path)
     }
 
-    fn path_str(&self) -> &str {
-        self.path
-            .to_str()
-            .expect("path did not contain valid unicode")
-    }
-
     fn get_valid_source_files(dir: &Path) -> io::Result<Vec<PathBuf>> {
         let entries = dir.read_dir()?;
         let mut sources = Vec::new();
@@ -165,66 +186,142 @@ impl<'a> Problem<'a> {
         Ok(file_path)
     }
 
-    /// Collects all pairs of test files from the "test" subfolder (a pair is
-    /// one `.in` file and one `.ans` file with the same name)
-    pub fn get_test_files(&self) -> Result<Vec<(PathBuf, PathBuf)>, StdErr> {
-        let test_path = self.path.join("test");
+    /// Collects all test cases of the problem. These are the official samples
+    /// in the test folders of its layout and the custom tests in "test/custom".
+    pub fn get_test_files(&self) -> Result<Vec<TestCase>, StdErr> {
+        Self::find_tests(&self.path)
+    }
 
-        if !test_path.exists() {
+    /// Collects all test cases of the problem in the given directory. A test
+    /// case is an input file along with the answer file of the same name, if
+    /// there is one. The samples come first, followed by the custom tests.
+    pub fn find_tests(problem_dir: &Path) -> Result<Vec<TestCase>, StdErr> {
+        let layout = TestLayout::for_problem(problem_dir)?;
+        let custom_dir = problem_dir.join(TEST_DIR_NAME).join(CUSTOM_TEST_DIR_NAME);
+
+        let dirs = layout
+            .dirs
+            .iter()
+            .map(|d| problem_dir.join(d))
+            .filter(|d| d.is_dir())
+            .collect::<Vec<_>>();
+
+        if dirs.is_empty() && !custom_dir.is_dir() {
             return Err(format!(
-                r#"subfolder "test" is missing in {}. consider using the --fetch flag to retrieve test files."#,
-                self.path_str()
+                r#"subfolder "{}" is missing in {}. consider using the --fetch flag to retrieve test files."#,
+                layout.dirs.join(r#"", ""#),
+                path_str(problem_dir)
             )
             .into());
         }
 
-        let mut in_files = HashMap::new();
-        let mut ans_files = HashMap::new();
+        // Tests are named by their path relative to the test folder, or to the
+        // folder containing all of them if there are several. The tests in
+        // data/sample and data/secret are thus named "sample/1", "secret/1" and
+        // so on.
+        let root = match layout.dirs.as_slice() {
+            [dir] => problem_dir.join(dir),
+            dirs => problem_dir.join(common_ancestor(dirs)),
+        };
+
+        let mut samples = Vec::new();
+        for dir in &dirs {
+            Self::collect_tests(dir, &layout, &custom_dir, &mut samples)?;
+        }
+        samples.sort();
+        samples.dedup();
+
+        let mut custom = Vec::new();
+        if custom_dir.is_dir() {
+            Self::collect_tests(&custom_dir, &layout, Path::new(""), &mut custom)?;
+        }
 
-        for entry in test_path.read_dir()? {
+        let mut sample_tests = samples
+            .into_iter()
+            .map(|input| Self::test_case(input, &root, "", &layout, false))
+            .collect::<Vec<_>>();
+        sample_tests.sort_by(|a, b| natural_cmp(&a.name, &b.name));
+
+        let custom_prefix = format!("{}/", CUSTOM_TEST_DIR_NAME);
+        let mut custom_tests = custom
+            .into_iter()
+            .map(|input| Self::test_case(input, &custom_dir, &custom_prefix, &layout, true))
+            .collect::<Vec<_>>();
+        custom_tests.sort_by(|a, b| natural_cmp(&a.name, &b.name));
+
+        sample_tests.extend(custom_tests);
+
+        Ok(sample_tests)
+    }
+
+    /// Finds the input files in a directory, and in its subdirectories if the
+    /// layout is recursive. The directory `skip` is left out.
+    fn collect_tests(
+        dir: &Path,
+        layout: &TestLayout,
+        skip: &Path,
+        inputs: &mut Vec<PathBuf>,
+    ) -> Result<(), StdErr> {
+        for entry in dir.read_dir()? {
             let path = entry?.path();
 
-            if !path.is_file() {
+            if path.is_dir() {
+                if layout.recursive && path != skip {
+                    Self::collect_tests(&path, layout, skip, inputs)?;
+                }
                 continue;
             }
 
-            let ext = match path.extension() {
-                Some(e) => match e.to_str() {
-                    Some(e) => e,
-                    None => continue,
-                },
+            let ext = match path.extension().and_then(|e| e.to_str()) {
+                Some(e) => e,
                 None => continue,
-            }
-            .to_lowercase();
-
-            // We can unwrap because the file extension check would ensure
-            // that the file was skipped if it did not have a valid name
-            let name = path.file_stem().unwrap().to_str().unwrap().to_string();
+            };
 
-            if ext == "in" {
-                in_files.insert(name, path);
-            } else if ext == "ans" {
-                ans_files.insert(name, path);
+            // Files with names that are not valid unicode are skipped, so the
+            // names of test cases can always be unwrapped later.
+            let is_input = layout
+                .input_extensions
+                .iter()
+                .any(|e| e.eq_ignore_ascii_case(ext));
+            if path.to_str().is_some() && is_input {
+                inputs.push(path);
             }
         }
 
-        let mut test_files = Vec::new();
+        Ok(())
+    }
 
-        for (in_key, in_file) in in_files.iter() {
-            if let Some(ans_file) = ans_files.get(in_key) {
-                test_files.push((in_file.clone(), ans_file.clone()))
-            }
+    fn test_case(
+        input: PathBuf,
+        root: &Path,
+        prefix: &str,
+        layout: &TestLayout,
+        custom: bool,
+    ) -> TestCase {
+        let relative = input
+            .strip_prefix(root)
+            .unwrap_or(&input)
+            .with_extension("");
+        let name = relative
+            .components()
+            .map(|c| c.as_os_str().to_str().unwrap())
+            .collect::<Vec<_>>()
+            .join("/");
+
+        // Extensions are matched regardless of case, like those of inputs.
+        let answer = layout
+            .answer_extensions
+            .iter()
+            .flat_map(|ext| vec![ext.clone(), ext.to_lowercase(), ext.to_uppercase()])
+            .map(|ext| input.with_extension(ext))
+            .find(|a| a.is_file());
+
+        TestCase {
+            name: format!("{}{}", prefix, name),
+            input,
+            answer,
+            custom,
         }
-
-        test_files.sort_by(|a, b| {
-            // We can unwrap for the same reason as before.
-            let a_name = a.0.file_stem().unwrap().to_str().unwrap();
-            let b_name = b.0.file_stem().unwrap().to_str().unwrap();
-
-            a_name.to_lowercase().cmp(&b_name.to_lowercase())
-        });
-
-        Ok(test_files)
     }
 
     pub fn lang(&self) -> &Language {
@@ -242,4 +339,32 @@ impl<'a> Problem<'a> {
     pub fn path(&self) -> PathBuf {
         self.path.clone()
     }
+
+    pub fn state_dir(&self) -> PathBuf {
+        self.path.join(STATE_DIR_NAME)
+    }
+
+    /// Reads the problem's metadata file. This is read anew on every call so
+    /// that changes are picked up while watching.
+    pub fn metadata(&self) -> Result<Metadata, StdErr> {
+        Metadata::load(&self.path)
+    }
+}
+
+/// Finds the longest path that all the given relative paths start with.
+fn common_ancestor(paths: &[String]) -> PathBuf {
+    let mut ancestor = match paths.first() {
+        Some(p) => PathBuf::from(p),
+        None => return PathBuf::new(),
+    };
+
+    for path in &paths[1..] {
+        while !Path::new(path).starts_with(&ancestor) {
+            if !ancestor.pop() {
+                return PathBuf::new();
+            }
+        }
+    }
+
+    ancestor
 }
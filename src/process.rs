@@ -0,0 +1,411 @@
+use crate::StdErr;
+use std::io::{self, Read};
+use std::path::PathBuf;
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// How often a running process is checked for whether it has exited.
+pub const POLL_INTERVAL: Duration = Duration::from_millis(5);
+
+/// A limit value meaning that the resource should not be limited at all, as
+/// opposed to `None`, which leaves the limit inherited from kitty untouched.
+pub const UNLIMITED: u64 = u64::MAX;
+
+/// How much of stderr is kept. Anything beyond this is read and discarded.
+pub const STDERR_CAPTURE_LIMIT: u64 = 1024 * 1024;
+
+/// Limits on the resources a program may use. Sizes are in bytes. Apart from
+/// the output limit, which kitty enforces itself, the limits are applied with
+/// `setrlimit` before the program starts. They are only supported on Unix and
+/// are ignored elsewhere.
+#[derive(Debug, Default, Clone)]
+pub struct ResourceLimits {
+    /// The memory limit. It is applied to the size of the program's data
+    /// segment and private writable mappings rather than to its whole address
+    /// space, which runtimes like the JVM reserve far more of than they use.
+    pub memory: Option<u64>,
+    pub stack: Option<u64>,
+    pub processes: Option<u64>,
+    /// The maximum size of any file written by the program.
+    pub file_size: Option<u64>,
+    /// The CPU time in whole seconds after which the program is killed.
+    pub cpu_time: Option<u64>,
+    /// The maximum number of bytes the program may write to stdout.
+    pub output: Option<u64>,
+}
+
+/// Where a program runs and what it can reach, apart from the resources it
+/// may use.
+#[derive(Debug, Default, Clone)]
+pub struct Isolation {
+    /// The working directory of the program, or `None` to use kitty's.
+    pub working_dir: Option<PathBuf>,
+    /// Whether the program runs in a sandbox where only the working directory
+    /// is writable and the network cannot be reached. It is only supported
+    /// on Linux.
+    pub sandbox: bool,
+}
+
+/// The result of running a program to completion (or until it was stopped).
+#[derive(Debug)]
+pub struct Output {
+    /// The exit status of the program. This is `None` if the program was killed
+    /// because it exceeded its time limit.
+    pub status: Option<ExitStatus>,
+    /// Everything the program wrote to stdout, up to the output limit.
+    pub stdout: Vec<u8>,
+    /// The beginning of what the program wrote to stderr.
+    pub stderr: Vec<u8>,
+    /// Whether the program was killed for writing more than the output limit.
+    pub output_limit_exceeded: bool,
+    /// Wall-clock time from the program being spawned until it exited.
+    pub elapsed: Duration,
+    /// The resources used by the program, if the platform reports them.
+    pub usage: Option<Usage>,
+}
+
+/// The resources used by a program that has exited, as reported by the
+/// operating system. This includes any processes it spawned and waited for.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Usage {
+    /// User and system CPU time.
+    pub cpu_time: Duration,
+    /// The peak resident set size in bytes.
+    pub peak_memory: u64,
+}
+
+impl Output {
+    pub fn timed_out(&self) -> bool {
+        self.status.is_none()
+    }
+}
+
+/// Runs the given command with the given stdin while capturing stdout and
+/// stderr. If the program has not exited once `time_limit` has passed, or if
+/// it exceeds the output limit, it is killed along with every process it has
+/// spawned.
+pub fn run(
+    cmd: &[String],
+    stdin: Stdio,
+    time_limit: Duration,
+    limits: &ResourceLimits,
+    isolation: &Isolation,
+) -> Result<Output, StdErr> {
+    let start_time = Instant::now();
+    let mut child = spawn(cmd, stdin, Stdio::piped(), limits, isolation)?;
+
+    // The output is read on separate threads such that the program never
+    // blocks on a full pipe while we wait for it to exit.
+    let output_limit_exceeded = Arc::new(AtomicBool::new(false));
+    let stdout_reader = child.stdout.take().map(|pipe| {
+        let limit = limits.output.unwrap_or(UNLIMITED);
+        read_in_background(pipe, limit, Some(Arc::clone(&output_limit_exceeded)))
+    });
+    let stderr_reader = child
+        .stderr
+        .take()
+        .map(|pipe| read_in_background(pipe, STDERR_CAPTURE_LIMIT, None));
+
+    let deadline = start_time + time_limit;
+    let (status, usage) = loop {
+        match try_wait(&mut child) {
+            Ok(Some((status, usage))) => break (Some(status), usage),
+            Ok(None) => {}
+            Err(_) => {
+                kill_tree(&mut child);
+                return Err("failed to wait for program to exit".into());
+            }
+        }
+
+        if output_limit_exceeded.load(Ordering::SeqCst) {
+            kill_tree(&mut child);
+            match wait(&mut child) {
+                Ok((status, usage)) => break (Some(status), usage),
+                Err(_) => return Err("failed to wait for program to exit".into()),
+            }
+        }
+
+        if Instant::now() >= deadline {
+            kill_tree(&mut child);
+            let usage = wait(&mut child).ok().and_then(|(_, usage)| usage);
+            break (None, usage);
+        }
+
+        thread::sleep(POLL_INTERVAL);
+    };
+    let elapsed = start_time.elapsed();
+
+    // Processes spawned by the program may outlive it and keep its output
+    // pipes open, so they are cleaned up as well.
+    kill_tree(&mut child);
+
+    let stdout = join_reader(stdout_reader)?;
+    let stderr = join_reader(stderr_reader)?;
+
+    Ok(Output {
+        status,
+        stdout,
+        stderr,
+        output_limit_exceeded: output_limit_exceeded.load(Ordering::SeqCst),
+        elapsed,
+        usage,
+    })
+}
+
+/// Checks whether the program has exited without blocking. If it has, its exit
+/// status is returned along with its resource usage.
+#[cfg(unix)]
+pub fn try_wait(child: &mut Child) -> io::Result<Option<(ExitStatus, Option<Usage>)>> {
+    wait4(child, libc::WNOHANG)
+}
+
+#[cfg(not(unix))]
+pub fn try_wait(child: &mut Child) -> io::Result<Option<(ExitStatus, Option<Usage>)>> {
+    Ok(child.try_wait()?.map(|status| (status, None)))
+}
+
+/// Waits for the program to exit and returns its exit status along with its
+/// resource usage.
+#[cfg(unix)]
+pub fn wait(child: &mut Child) -> io::Result<(ExitStatus, Option<Usage>)> {
+    match wait4(child, 0)? {
+        Some(result) => Ok(result),
+        None => Err(io::Error::other("program had not exited")),
+    }
+}
+
+#[cfg(not(unix))]
+pub fn wait(child: &mut Child) -> io::Result<(ExitStatus, Option<Usage>)> {
+    Ok((child.wait()?, None))
+}
+
+/// Waits for the program with `wait4`, which unlike `Child::wait` also gives
+/// the resource usage of the program. Note that the `Child` does not learn
+/// that the program was reaped, so it must not be waited for or killed through
+/// `Child` afterwards.
+#[cfg(unix)]
+fn wait4(
+    child: &mut Child,
+    options: libc::c_int,
+) -> io::Result<Option<(ExitStatus, Option<Usage>)>> {
+    use std::os::unix::process::ExitStatusExt;
+
+    let mut status = 0;
+    let mut rusage = unsafe { std::mem::zeroed::<libc::rusage>() };
+
+    loop {
+        let pid =
+            unsafe { libc::wait4(child.id() as libc::pid_t, &mut status, options, &mut rusage) };
+
+        match pid {
+            0 => return Ok(None),
+            -1 => {
+                let err = io::Error::last_os_error();
+                if err.kind() != io::ErrorKind::Interrupted {
+                    return Err(err);
+                }
+            }
+            _ => return Ok(Some((ExitStatus::from_raw(status), Some(usage(&rusage))))),
+        }
+    }
+}
+
+#[cfg(unix)]
+fn usage(rusage: &libc::rusage) -> Usage {
+    let to_duration = |t: libc::timeval| {
+        Duration::from_secs(t.tv_sec as u64) + Duration::from_micros(t.tv_usec as u64)
+    };
+
+    // Linux reports the peak memory in kilobytes, while macOS uses bytes.
+    let peak_memory = if cfg!(target_os = "macos") {
+        rusage.ru_maxrss as u64
+    } else {
+        rusage.ru_maxrss as u64 * 1024
+    };
+
+    Usage {
+        cpu_time: to_duration(rusage.ru_utime) + to_duration(rusage.ru_stime),
+        peak_memory,
+    }
+}
+
+/// Spawns the command with the given limits and isolation applied and stderr
+/// piped. The program is put in its own process group such that it can be
+/// killed along with all of its descendants using `kill_tree`.
+pub fn spawn(
+    cmd: &[String],
+    stdin: Stdio,
+    stdout: Stdio,
+    limits: &ResourceLimits,
+    isolation: &Isolation,
+) -> Result<Child, StdErr> {
+    let (prog, args) = match cmd.split_first() {
+        Some(p) => p,
+        None => return Err("command was empty".into()),
+    };
+
+    let mut command = Command::new(prog);
+    command
+        .args(args)
+        .stdin(stdin)
+        .stdout(stdout)
+        .stderr(Stdio::piped());
+
+    if let Some(dir) = &isolation.working_dir {
+        command.current_dir(dir);
+    }
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+        command.process_group(0);
+        apply_limits(&mut command, limits.clone());
+    }
+    #[cfg(not(unix))]
+    let _ = limits;
+
+    #[cfg(target_os = "linux")]
+    {
+        if isolation.sandbox {
+            let dir = match &isolation.working_dir {
+                Some(d) => d,
+                None => return Err("the sandbox needs a working directory".into()),
+            };
+
+            if let Err(e) = crate::sandbox::apply(&mut command, dir) {
+                return Err(format!("failed to set up the sandbox: {}", e).into());
+            }
+        }
+    }
+
+    match command.spawn() {
+        Ok(c) => Ok(c),
+        // Setting up the namespaces fails if unprivileged user namespaces are
+        // disabled, which some distributions do.
+        Err(e) if isolation.sandbox => Err(format!(
+            "failed to execute command \"{}\" in the sandbox: {}. it needs Linux 5.12 or later with unprivileged user namespaces enabled",
+            prog, e
+        )
+        .into()),
+        Err(_) => Err(format!("failed to execute command \"{}\"", prog).into()),
+    }
+}
+
+/// Reads everything from the pipe on a separate thread, keeping at most `limit`
+/// bytes. If more than that is written, `exceeded` is set, and the rest is
+/// discarded such that the writer is never blocked.
+pub fn read_in_background<R: Read + Send + 'static>(
+    mut pipe: R,
+    limit: u64,
+    exceeded: Option<Arc<AtomicBool>>,
+) -> JoinHandle<io::Result<Vec<u8>>> {
+    thread::spawn(move || {
+        let mut buf = Vec::new();
+        (&mut pipe).take(limit).read_to_end(&mut buf)?;
+
+        let mut discard_buf = [0; 8192];
+        loop {
+            match pipe.read(&mut discard_buf) {
+                Ok(0) => break,
+                Ok(_) => {
+                    if let Some(flag) = &exceeded {
+                        flag.store(true, Ordering::SeqCst);
+                    }
+                }
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+
+        Ok(buf)
+    })
+}
+
+pub fn join_reader(reader: Option<JoinHandle<io::Result<Vec<u8>>>>) -> Result<Vec<u8>, StdErr> {
+    match reader.map(JoinHandle::join) {
+        Some(Ok(Ok(buf))) => Ok(buf),
+        None => Ok(Vec::new()),
+        _ => Err("failed to read program output".into()),
+    }
+}
+
+#[cfg(all(target_os = "linux", target_env = "gnu"))]
+type Resource = libc::__rlimit_resource_t;
+#[cfg(all(unix, not(all(target_os = "linux", target_env = "gnu"))))]
+type Resource = libc::c_int;
+
+#[cfg(unix)]
+fn apply_limits(command: &mut Command, limits: ResourceLimits) {
+    use std::os::unix::process::CommandExt;
+
+    // Safety: the closure runs in the forked child before exec, so it must only
+    // make async-signal-safe calls, which getrlimit and setrlimit are.
+    unsafe {
+        command.pre_exec(move || {
+            set_limit(libc::RLIMIT_DATA, limits.memory)?;
+            set_limit(libc::RLIMIT_STACK, limits.stack)?;
+            set_limit(libc::RLIMIT_NPROC, limits.processes)?;
+            set_limit(libc::RLIMIT_FSIZE, limits.file_size)?;
+            set_limit(libc::RLIMIT_CPU, limits.cpu_time)?;
+            Ok(())
+        });
+    }
+}
+
+/// Sets the soft limit of the given resource. Since raising the hard limit
+/// requires privileges, the soft limit is capped at the hard limit.
+#[cfg(unix)]
+fn set_limit(resource: Resource, value: Option<u64>) -> io::Result<()> {
+    let value = match value {
+        Some(v) => v,
+        None => return Ok(()),
+    };
+
+    let mut limit = libc::rlimit {
+        rlim_cur: 0,
+        rlim_max: 0,
+    };
+
+    if unsafe { libc::getrlimit(resource, &mut limit) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    let wanted = if value == UNLIMITED {
+        libc::RLIM_INFINITY
+    } else {
+        value as libc::rlim_t
+    };
+    limit.rlim_cur = wanted.min(limit.rlim_max);
+
+    if unsafe { libc::setrlimit(resource, &limit) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    Ok(())
+}
+
+/// Kills the process along with every process in its process group.
+#[cfg(unix)]
+pub fn kill_tree(child: &mut Child) {
+    // The process was spawned as the leader of its own process group, so the
+    // group id is the same as the process id. The process itself is killed
+    // through the group as well, since it may already have been reaped with
+    // `wait4`, in which case `Child::kill` could hit an unrelated process.
+    unsafe {
+        libc::kill(-(child.id() as libc::pid_t), libc::SIGKILL);
+    }
+}
+
+/// Kills the process along with every process it has spawned.
+#[cfg(windows)]
+pub fn kill_tree(child: &mut Child) {
+    let _ = Command::new("taskkill")
+        .args(&["/T", "/F", "/PID", &child.id().to_string()])
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status();
+    let _ = child.kill();
+}
@@ -0,0 +1,396 @@
+use crate::verdict::Verdict;
+use serde_json::json;
+use std::time::Duration;
+
+/// How much of the output of a program is included in a report.
+const OUTPUT_LIMIT: usize = 4096;
+
+/// A machine-readable format for the results of a test run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReportFormat {
+    Json,
+    Junit,
+    Tap,
+}
+
+impl ReportFormat {
+    pub const NAMES: &'static [&'static str] = &["json", "junit", "tap"];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "json" => Some(ReportFormat::Json),
+            "junit" => Some(ReportFormat::Junit),
+            "tap" => Some(ReportFormat::Tap),
+            _ => None,
+        }
+    }
+}
+
+/// The outcome of compiling the solution or one of the programs that judge it.
+#[derive(Debug)]
+pub struct CompileRecord {
+    /// What was compiled, e.g. "program" or "validator".
+    pub target: String,
+    pub success: bool,
+    pub timed_out: bool,
+    pub elapsed: Duration,
+    /// The beginning of the messages printed by the compiler.
+    pub output: String,
+}
+
+/// The outcome of a single test case.
+#[derive(Debug)]
+pub struct TestRecord {
+    pub name: String,
+    /// The verdict, or `None` if the test case has no answer to compare the
+    /// output to.
+    pub verdict: Option<Verdict>,
+    /// Wall-clock time.
+    pub elapsed: Duration,
+    pub cpu_time: Option<Duration>,
+    /// Peak memory usage in bytes.
+    pub peak_memory: Option<u64>,
+    /// A description of how the program exited, or `None` if it timed out.
+    pub exit_status: Option<String>,
+    pub exit_code: Option<i32>,
+    /// The beginning of the output, as given by `excerpt`.
+    pub stdout: String,
+    pub stderr: String,
+    pub judge_message: Option<String>,
+}
+
+impl TestRecord {
+    fn failed(&self) -> bool {
+        self.verdict.is_some_and(|v| !v.is_passing())
+    }
+}
+
+/// Everything that happened in a test run.
+#[derive(Debug, Default)]
+pub struct Report {
+    pub problem: String,
+    pub compilations: Vec<CompileRecord>,
+    pub tests: Vec<TestRecord>,
+}
+
+impl Report {
+    pub fn render(&self, format: ReportFormat) -> String {
+        match format {
+            ReportFormat::Json => self.to_json(),
+            ReportFormat::Junit => self.to_junit(),
+            ReportFormat::Tap => self.to_tap(),
+        }
+    }
+
+    /// Whether everything compiled and no test case failed.
+    pub fn passed(&self) -> bool {
+        self.compilations.iter().all(|c| c.success) && !self.tests.iter().any(TestRecord::failed)
+    }
+
+    fn to_json(&self) -> String {
+        let compilations = self
+            .compilations
+            .iter()
+            .map(|c| {
+                json!({
+                    "target": c.target,
+                    "success": c.success,
+                    "timed_out": c.timed_out,
+                    "time": c.elapsed.as_secs_f64(),
+                    "output": c.output,
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let tests = self
+            .tests
+            .iter()
+            .map(|t| {
+                json!({
+                    "name": t.name,
+                    "verdict": t.verdict.map(Verdict::name),
+                    "passed": t.verdict.map(Verdict::is_passing),
+                    "time": t.elapsed.as_secs_f64(),
+                    "cpu_time": t.cpu_time.map(|d| d.as_secs_f64()),
+                    "peak_memory": t.peak_memory,
+                    "exit_status": t.exit_status,
+                    "exit_code": t.exit_code,
+                    "stdout": t.stdout,
+                    "stderr": t.stderr,
+                    "judge_message": t.judge_message,
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let report = json!({
+            "problem": self.problem,
+            "passed": self.passed(),
+            "compilations": compilations,
+            "tests": tests,
+        });
+
+        // Serialising a JSON value cannot fail.
+        let mut text = serde_json::to_string_pretty(&report).unwrap();
+        text.push('\n');
+        text
+    }
+
+    fn to_junit(&self) -> String {
+        let failures = self.tests.iter().filter(|t| t.failed()).count()
+            + self.compilations.iter().filter(|c| !c.success).count();
+        let skipped = self.tests.iter().filter(|t| t.verdict.is_none()).count();
+        let total_time: f64 = self
+            .compilations
+            .iter()
+            .map(|c| c.elapsed.as_secs_f64())
+            .sum::<f64>()
+            + self
+                .tests
+                .iter()
+                .map(|t| t.elapsed.as_secs_f64())
+                .sum::<f64>();
+
+        let mut xml = String::new();
+        xml.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        xml.push_str("<testsuites>\n");
+        xml.push_str(&format!(
+            "  <testsuite name=\"{}\" tests=\"{}\" failures=\"{}\" errors=\"0\" skipped=\"{}\" time=\"{:.3}\">\n",
+            escape_xml(&self.problem),
+            self.compilations.len() + self.tests.len(),
+            failures,
+            skipped,
+            total_time
+        ));
+
+        for c in &self.compilations {
+            xml.push_str(&format!(
+                "    <testcase name=\"compile {}\" classname=\"{}.compile\" time=\"{:.3}\">\n",
+                escape_xml(&c.target),
+                escape_xml(&self.problem),
+                c.elapsed.as_secs_f64()
+            ));
+            if !c.success {
+                let message = if c.timed_out {
+                    "compilation timed out"
+                } else {
+                    "compilation error"
+                };
+                xml.push_str(&format!(
+                    "      <failure message=\"{}\" type=\"{}\">{}</failure>\n",
+                    message,
+                    message,
+                    escape_xml(&c.output)
+                ));
+            }
+            xml.push_str("    </testcase>\n");
+        }
+
+        for t in &self.tests {
+            xml.push_str(&format!(
+                "    <testcase name=\"{}\" classname=\"{}\" time=\"{:.3}\">\n",
+                escape_xml(&t.name),
+                escape_xml(&self.problem),
+                t.elapsed.as_secs_f64()
+            ));
+            match t.verdict {
+                Some(verdict) if !verdict.is_passing() => xml.push_str(&format!(
+                    "      <failure message=\"{}\" type=\"{}\">{}</failure>\n",
+                    verdict,
+                    verdict,
+                    escape_xml(t.judge_message.as_deref().unwrap_or_default())
+                )),
+                Some(_) => {}
+                None => xml.push_str("      <skipped message=\"no answer to compare\"/>\n"),
+            }
+            xml.push_str(&format!(
+                "      <system-out>{}</system-out>\n",
+                escape_xml(&t.stdout)
+            ));
+            xml.push_str(&format!(
+                "      <system-err>{}</system-err>\n",
+                escape_xml(&t.stderr)
+            ));
+            xml.push_str("    </testcase>\n");
+        }
+
+        xml.push_str("  </testsuite>\n");
+        xml.push_str("</testsuites>\n");
+        xml
+    }
+
+    fn to_tap(&self) -> String {
+        let mut tap = String::new();
+        tap.push_str("TAP version 13\n");
+        tap.push_str(&format!(
+            "1..{}\n",
+            self.compilations.len() + self.tests.len()
+        ));
+
+        let mut number = 0;
+
+        for c in &self.compilations {
+            number += 1;
+            let status = if c.success { "ok" } else { "not ok" };
+            tap.push_str(&format!("{} {} - compile {}\n", status, number, c.target));
+
+            let mut fields = vec![("time", format!("{:.3}", c.elapsed.as_secs_f64()))];
+            if c.timed_out {
+                fields.push(("timed_out", "true".to_string()));
+            }
+            if !c.success {
+                fields.push(("output", c.output.clone()));
+            }
+            push_yaml_block(&mut tap, &fields);
+        }
+
+        for t in &self.tests {
+            number += 1;
+            match t.verdict {
+                Some(verdict) if !verdict.is_passing() => {
+                    tap.push_str(&format!("not ok {} - {} # {}\n", number, t.name, verdict))
+                }
+                Some(_) => tap.push_str(&format!("ok {} - {}\n", number, t.name)),
+                None => tap.push_str(&format!(
+                    "ok {} - {} # SKIP no answer to compare\n",
+                    number, t.name
+                )),
+            }
+
+            let mut fields = Vec::new();
+            if let Some(verdict) = t.verdict {
+                fields.push(("verdict", verdict.to_string()));
+            }
+            fields.push(("time", format!("{:.3}", t.elapsed.as_secs_f64())));
+            if let Some(cpu_time) = t.cpu_time {
+                fields.push(("cpu_time", format!("{:.3}", cpu_time.as_secs_f64())));
+            }
+            if let Some(peak_memory) = t.peak_memory {
+                fields.push(("peak_memory", peak_memory.to_string()));
+            }
+            fields.push((
+                "exit_status",
+                t.exit_status
+                    .clone()
+                    .unwrap_or_else(|| "timed out".to_string()),
+            ));
+            if t.failed() {
+                if let Some(message) = &t.judge_message {
+                    fields.push(("judge_message", message.clone()));
+                }
+                fields.push(("stdout", t.stdout.clone()));
+                fields.push(("stderr", t.stderr.clone()));
+            }
+            push_yaml_block(&mut tap, &fields);
+        }
+
+        tap
+    }
+}
+
+/// Adds a YAML diagnostic block to a TAP test line. Values that could be
+/// misread are written as literal block scalars so that they never need
+/// escaping.
+fn push_yaml_block(tap: &mut String, fields: &[(&str, String)]) {
+    tap.push_str("  ---\n");
+
+    for (key, value) in fields {
+        if value.is_empty() {
+            tap.push_str(&format!("  {}: ''\n", key));
+            continue;
+        }
+
+        let is_plain = value
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, ' ' | '.' | '_' | '-'))
+            && !value.starts_with([' ', '-'])
+            && !value.ends_with(' ');
+        if is_plain {
+            tap.push_str(&format!("  {}: {}\n", key, value));
+            continue;
+        }
+
+        // The indentation of a block is taken from its first line that is not
+        // empty, so it must be given if that line starts with spaces.
+        let starts_indented = value
+            .lines()
+            .find(|l| !l.is_empty())
+            .is_some_and(|l| l.starts_with(' '));
+        let indicator = if starts_indented { "2" } else { "" };
+
+        tap.push_str(&format!("  {}: |{}-\n", key, indicator));
+        for line in value.lines() {
+            tap.push_str(&format!("    {}\n", line));
+        }
+    }
+
+    tap.push_str("  ...\n");
+}
+
+/// Gives the beginning of the output of a program as text, noting how much was
+/// left out if it is longer than `OUTPUT_LIMIT` bytes.
+pub fn excerpt(output: &[u8]) -> String {
+    if output.len() <= OUTPUT_LIMIT {
+        return String::from_utf8_lossy(output).into_owned();
+    }
+
+    format!(
+        "{}\n[truncated, {} bytes in total]",
+        String::from_utf8_lossy(&output[..OUTPUT_LIMIT]),
+        output.len()
+    )
+}
+
+fn escape_xml(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            // These control characters are not allowed in XML at all.
+            c if c.is_control() && !matches!(c, '\n' | '\r' | '\t') => {}
+            c => escaped.push(c),
+        }
+    }
+
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn yaml_block(value: &str) -> String {
+        let mut tap = String::new();
+        push_yaml_block(&mut tap, &[("stdout", value.to_string())]);
+        tap
+    }
+
+    #[test]
+    fn yaml_block_uses_plain_scalars_when_possible() {
+        assert_eq!(
+            yaml_block("wrong answer"),
+            "  ---\n  stdout: wrong answer\n  ...\n"
+        );
+        assert_eq!(yaml_block(""), "  ---\n  stdout: ''\n  ...\n");
+    }
+
+    #[test]
+    fn yaml_block_gives_indentation_of_indented_text() {
+        assert_eq!(
+            yaml_block("1 2\n  3"),
+            "  ---\n  stdout: |-\n    1 2\n      3\n  ...\n"
+        );
+        assert_eq!(
+            yaml_block("  1 2\n3"),
+            "  ---\n  stdout: |2-\n      1 2\n    3\n  ...\n"
+        );
+        assert_eq!(
+            yaml_block("\n  1"),
+            "  ---\n  stdout: |2-\n    \n      1\n  ...\n"
+        );
+    }
+}
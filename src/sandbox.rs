@@ -0,0 +1,443 @@
+use std::ffi::CString;
+use std::io;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::process::CommandExt;
+use std::path::Path;
+use std::process::Command;
+use std::ptr;
+
+/// The system calls a solution never needs, which could be used to escape the
+/// sandbox or to affect the rest of the system. A program making one of them
+/// is killed with SIGSYS, which is reported as a run-time error.
+const BLOCKED_SYSCALLS: &[libc::c_long] = &[
+    libc::SYS_ptrace,
+    libc::SYS_process_vm_readv,
+    libc::SYS_process_vm_writev,
+    libc::SYS_mount,
+    libc::SYS_umount2,
+    libc::SYS_pivot_root,
+    libc::SYS_chroot,
+    libc::SYS_unshare,
+    libc::SYS_setns,
+    libc::SYS_open_by_handle_at,
+    libc::SYS_bpf,
+    libc::SYS_perf_event_open,
+    libc::SYS_userfaultfd,
+    libc::SYS_keyctl,
+    libc::SYS_add_key,
+    libc::SYS_request_key,
+    libc::SYS_kexec_load,
+    libc::SYS_init_module,
+    libc::SYS_finit_module,
+    libc::SYS_delete_module,
+    libc::SYS_reboot,
+    libc::SYS_swapon,
+    libc::SYS_swapoff,
+    libc::SYS_acct,
+    libc::SYS_settimeofday,
+    libc::SYS_clock_settime,
+    // Requests made through io_uring, such as creating sockets, are not seen
+    // by seccomp.
+    libc::SYS_io_uring_setup,
+    libc::SYS_io_uring_enter,
+    libc::SYS_io_uring_register,
+    SYS_MOUNT_SETATTR,
+];
+
+/// The system calls that create sockets. They fail for Unix domain sockets,
+/// since the network namespace does not keep the program from connecting to
+/// the sockets of services on the host through the file system, such as that
+/// of Docker. They fail rather than kill the program, since the C library may
+/// try such sockets on its own when looking up users.
+const SOCKET_SYSCALLS: &[libc::c_long] = &[libc::SYS_socket, libc::SYS_socketpair];
+
+// The system call is newer than some versions of the libc crate. It has the
+// same number on every architecture.
+const SYS_MOUNT_SETATTR: libc::c_long = 442;
+const MOUNT_ATTR_RDONLY: u64 = 0x1;
+const AT_RECURSIVE: libc::c_uint = 0x8000;
+
+/// The argument of `mount_setattr`.
+#[repr(C)]
+struct MountAttr {
+    attr_set: u64,
+    attr_clr: u64,
+    propagation: u64,
+    userns_fd: u64,
+}
+
+// Seccomp and BPF constants from the kernel headers, which not every version
+// of the libc crate has.
+const SECCOMP_RET_KILL_PROCESS: u32 = 0x8000_0000;
+const SECCOMP_RET_ERRNO: u32 = 0x0005_0000;
+const SECCOMP_RET_ALLOW: u32 = 0x7fff_0000;
+const BPF_LD_W_ABS: u16 = 0x20;
+const BPF_JMP_JEQ_K: u16 = 0x15;
+const BPF_JMP_JGE_K: u16 = 0x35;
+const BPF_RET_K: u16 = 0x06;
+
+/// Offsets of the fields of `struct seccomp_data`.
+const SECCOMP_DATA_NR: u32 = 0;
+const SECCOMP_DATA_ARCH: u32 = 4;
+/// The lower half of the first argument on little-endian architectures, which
+/// are the only ones the sandbox supports. The socket domain is an `int`, so
+/// the upper half is ignored by the kernel.
+const SECCOMP_DATA_ARG0: u32 = 16;
+
+#[cfg(target_arch = "x86_64")]
+const AUDIT_ARCH: Option<u32> = Some(0xc000_003e);
+#[cfg(target_arch = "aarch64")]
+const AUDIT_ARCH: Option<u32> = Some(0xc000_00b7);
+#[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+const AUDIT_ARCH: Option<u32> = None;
+
+/// System calls of the x32 ABI on x86_64 have this bit set. They are the same
+/// calls under other numbers, so they are blocked altogether.
+#[cfg(target_arch = "x86_64")]
+const X32_SYSCALL_BIT: Option<u32> = Some(0x4000_0000);
+#[cfg(not(target_arch = "x86_64"))]
+const X32_SYSCALL_BIT: Option<u32> = None;
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct SockFilter {
+    code: u16,
+    jt: u8,
+    jf: u8,
+    k: u32,
+}
+
+#[repr(C)]
+struct SockFprog {
+    len: libc::c_ushort,
+    filter: *const SockFilter,
+}
+
+/// Makes the command run in new user, mount and network namespaces, which
+/// needs no privileges. The program sees the file system read-only apart
+/// from `writable_dir`, which becomes its working directory, it has no
+/// network, and a seccomp filter kills it if it makes one of
+/// `BLOCKED_SYSCALLS`. Creating Unix domain sockets fails.
+pub fn apply(command: &mut Command, writable_dir: &Path) -> io::Result<()> {
+    let arch = match AUDIT_ARCH {
+        Some(a) => a,
+        None => {
+            return Err(io::Error::other(
+                "the sandbox is not supported on this architecture",
+            ))
+        }
+    };
+
+    // Everything is prepared before forking, since the child may only make
+    // async-signal-safe calls and so must not allocate.
+    let filter = seccomp_filter(arch);
+    let dir = match CString::new(writable_dir.as_os_str().as_bytes()) {
+        Ok(d) => d,
+        Err(_) => return Err(io::Error::from(io::ErrorKind::InvalidInput)),
+    };
+    let (uid, gid) = unsafe { (libc::getuid(), libc::getgid()) };
+    // The program keeps its own user and group IDs inside the namespace, so
+    // the files it can read are the same.
+    let uid_map = format!("{} {} 1", uid, uid);
+    let gid_map = format!("{} {} 1", gid, gid);
+
+    // Safety: the closure runs in the forked child before exec, and only makes
+    // system calls on memory prepared above.
+    unsafe {
+        command.pre_exec(move || {
+            check(libc::unshare(
+                libc::CLONE_NEWUSER | libc::CLONE_NEWNS | libc::CLONE_NEWNET,
+            ))?;
+
+            write_file(b"/proc/self/setgroups\0", b"deny")?;
+            write_file(b"/proc/self/uid_map\0", uid_map.as_bytes())?;
+            write_file(b"/proc/self/gid_map\0", gid_map.as_bytes())?;
+
+            // Keeps the mounts below from reaching the rest of the system.
+            let root = b"/\0".as_ptr() as *const libc::c_char;
+            check(libc::mount(
+                ptr::null(),
+                root,
+                ptr::null(),
+                libc::MS_REC | libc::MS_PRIVATE,
+                ptr::null(),
+            ))?;
+
+            // The bind mount lets the directory be made writable on its own
+            // after everything else is made read-only.
+            check(libc::mount(
+                dir.as_ptr(),
+                dir.as_ptr(),
+                ptr::null(),
+                libc::MS_BIND | libc::MS_REC,
+                ptr::null(),
+            ))?;
+            set_mount_attr(root, AT_RECURSIVE, MOUNT_ATTR_RDONLY, 0)?;
+            set_mount_attr(dir.as_ptr(), 0, 0, MOUNT_ATTR_RDONLY)?;
+
+            // The working directory was entered before the bind mount, so it
+            // still refers to the read-only directory underneath.
+            check(libc::chdir(dir.as_ptr()))?;
+
+            let program = SockFprog {
+                len: filter.len() as libc::c_ushort,
+                filter: filter.as_ptr(),
+            };
+            check(libc::prctl(libc::PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0))?;
+            check(libc::prctl(
+                libc::PR_SET_SECCOMP,
+                libc::SECCOMP_MODE_FILTER,
+                &program as *const SockFprog,
+            ))?;
+
+            Ok(())
+        });
+    }
+
+    Ok(())
+}
+
+/// Builds a BPF program that kills the process if it makes a system call for
+/// another architecture or one of `BLOCKED_SYSCALLS`, makes creating Unix
+/// domain sockets fail, and allows anything else.
+fn seccomp_filter(arch: u32) -> Vec<SockFilter> {
+    let statement = |code, k| SockFilter {
+        code,
+        jt: 0,
+        jf: 0,
+        k,
+    };
+
+    let mut blocked = BLOCKED_SYSCALLS
+        .iter()
+        .map(|&nr| (BPF_JMP_JEQ_K, nr as u32))
+        .collect::<Vec<_>>();
+    if let Some(bit) = X32_SYSCALL_BIT {
+        blocked.push((BPF_JMP_JGE_K, bit));
+    }
+
+    let mut filter = vec![
+        statement(BPF_LD_W_ABS, SECCOMP_DATA_ARCH),
+        // Skips the next instruction if the architecture matches.
+        SockFilter {
+            code: BPF_JMP_JEQ_K,
+            jt: 1,
+            jf: 0,
+            k: arch,
+        },
+        statement(BPF_RET_K, SECCOMP_RET_KILL_PROCESS),
+        statement(BPF_LD_W_ABS, SECCOMP_DATA_NR),
+    ];
+
+    // The checks are followed by the instruction that allows the call, then
+    // the check of the socket domain, and last the instruction that kills the
+    // process. Jumps are relative to the next instruction.
+    let checks = blocked.len() + SOCKET_SYSCALLS.len();
+    let domain_check = checks + 1;
+    let kill = checks + 5;
+
+    for (i, &(code, k)) in blocked.iter().enumerate() {
+        filter.push(SockFilter {
+            code,
+            jt: (kill - i - 1) as u8,
+            jf: 0,
+            k,
+        });
+    }
+
+    for (i, &nr) in SOCKET_SYSCALLS.iter().enumerate() {
+        let i = blocked.len() + i;
+        filter.push(SockFilter {
+            code: BPF_JMP_JEQ_K,
+            jt: (domain_check - i - 1) as u8,
+            jf: 0,
+            k: nr as u32,
+        });
+    }
+
+    filter.push(statement(BPF_RET_K, SECCOMP_RET_ALLOW));
+    filter.push(statement(BPF_LD_W_ABS, SECCOMP_DATA_ARG0));
+    // Skips to the instruction that fails the call for Unix sockets.
+    filter.push(SockFilter {
+        code: BPF_JMP_JEQ_K,
+        jt: 1,
+        jf: 0,
+        k: libc::AF_UNIX as u32,
+    });
+    filter.push(statement(BPF_RET_K, SECCOMP_RET_ALLOW));
+    filter.push(statement(
+        BPF_RET_K,
+        SECCOMP_RET_ERRNO | libc::EACCES as u32,
+    ));
+    filter.push(statement(BPF_RET_K, SECCOMP_RET_KILL_PROCESS));
+
+    filter
+}
+
+/// Writes to a file without allocating. `path` must end with a nul byte.
+fn write_file(path: &[u8], content: &[u8]) -> io::Result<()> {
+    unsafe {
+        let fd = check(libc::open(
+            path.as_ptr() as *const libc::c_char,
+            libc::O_WRONLY,
+        ))?;
+        let written = libc::write(fd, content.as_ptr() as *const libc::c_void, content.len());
+        libc::close(fd);
+
+        if written != content.len() as isize {
+            return Err(io::Error::last_os_error());
+        }
+    }
+
+    Ok(())
+}
+
+/// Changes the flags of the mount at `path` with `mount_setattr`, which
+/// unlike `mount` leaves the flags that may not be changed inside a user
+/// namespace alone. It needs Linux 5.12 or later.
+fn set_mount_attr(
+    path: *const libc::c_char,
+    flags: libc::c_uint,
+    set: u64,
+    clear: u64,
+) -> io::Result<()> {
+    let attr = MountAttr {
+        attr_set: set,
+        attr_clr: clear,
+        propagation: 0,
+        userns_fd: 0,
+    };
+
+    let result = unsafe {
+        libc::syscall(
+            SYS_MOUNT_SETATTR,
+            libc::AT_FDCWD,
+            path,
+            flags,
+            &attr as *const MountAttr,
+            std::mem::size_of::<MountAttr>(),
+        )
+    };
+
+    check(result as libc::c_int).map(|_| ())
+}
+
+/// Turns the return value of a system call into an error if it failed.
+fn check(result: libc::c_int) -> io::Result<libc::c_int> {
+    if result == -1 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ARCH: u32 = 0xc000_003e;
+
+    /// Runs the filter on a system call like the kernel would, giving what it
+    /// returns.
+    fn run_filter(filter: &[SockFilter], arch: u32, nr: libc::c_long, arg0: u64) -> u32 {
+        let load = |offset| match offset {
+            SECCOMP_DATA_NR => nr as u32,
+            SECCOMP_DATA_ARCH => arch,
+            SECCOMP_DATA_ARG0 => arg0 as u32,
+            _ => panic!("load from unexpected offset {}", offset),
+        };
+
+        let mut accumulator = 0;
+        let mut pc = 0;
+        loop {
+            let instruction = filter[pc];
+            pc += 1;
+
+            match instruction.code {
+                BPF_LD_W_ABS => accumulator = load(instruction.k),
+                BPF_JMP_JEQ_K | BPF_JMP_JGE_K => {
+                    let taken = if instruction.code == BPF_JMP_JEQ_K {
+                        accumulator == instruction.k
+                    } else {
+                        accumulator >= instruction.k
+                    };
+                    pc += if taken {
+                        instruction.jt as usize
+                    } else {
+                        instruction.jf as usize
+                    };
+                }
+                BPF_RET_K => return instruction.k,
+                code => panic!("unexpected instruction {:#x}", code),
+            }
+        }
+    }
+
+    #[test]
+    fn filter_jumps_stay_within_the_program() {
+        let filter = seccomp_filter(ARCH);
+
+        for (i, instruction) in filter.iter().enumerate() {
+            if instruction.code == BPF_JMP_JEQ_K || instruction.code == BPF_JMP_JGE_K {
+                assert!(i + 1 + (instruction.jt as usize) < filter.len());
+                assert!(i + 1 + (instruction.jf as usize) < filter.len());
+            }
+        }
+        assert_eq!(filter.last().unwrap().code, BPF_RET_K);
+    }
+
+    #[test]
+    fn filter_allows_ordinary_system_calls() {
+        let filter = seccomp_filter(ARCH);
+
+        for &nr in &[
+            libc::SYS_read,
+            libc::SYS_write,
+            libc::SYS_mmap,
+            libc::SYS_exit_group,
+        ] {
+            assert_eq!(run_filter(&filter, ARCH, nr, 0), SECCOMP_RET_ALLOW);
+        }
+        for &nr in SOCKET_SYSCALLS {
+            let domain = libc::AF_INET as u64;
+            assert_eq!(run_filter(&filter, ARCH, nr, domain), SECCOMP_RET_ALLOW);
+        }
+    }
+
+    #[test]
+    fn filter_kills_blocked_system_calls() {
+        let filter = seccomp_filter(ARCH);
+
+        for &nr in BLOCKED_SYSCALLS {
+            assert_eq!(run_filter(&filter, ARCH, nr, 0), SECCOMP_RET_KILL_PROCESS);
+        }
+    }
+
+    #[test]
+    fn filter_fails_unix_sockets() {
+        let filter = seccomp_filter(ARCH);
+
+        for &nr in SOCKET_SYSCALLS {
+            let domain = libc::AF_UNIX as u64;
+            assert_eq!(
+                run_filter(&filter, ARCH, nr, domain),
+                SECCOMP_RET_ERRNO | libc::EACCES as u32
+            );
+        }
+    }
+
+    #[test]
+    fn filter_kills_other_architectures() {
+        let filter = seccomp_filter(ARCH);
+
+        assert_eq!(
+            run_filter(&filter, 0x4000_0003, libc::SYS_read, 0),
+            SECCOMP_RET_KILL_PROCESS
+        );
+
+        if let Some(bit) = X32_SYSCALL_BIT {
+            let nr = (bit as libc::c_long) | libc::SYS_read;
+            assert_eq!(run_filter(&filter, ARCH, nr, 0), SECCOMP_RET_KILL_PROCESS);
+        }
+    }
+}
@@ -0,0 +1,199 @@
+use crate::metadata::Metadata;
+use crate::problem::TEST_DIR_NAME;
+use crate::StdErr;
+use crate::CFG as cfg;
+use std::path::Path;
+use yaml_rust::yaml::Hash;
+use yaml_rust::Yaml;
+
+/// Where the test cases of a problem are found, as given under the `tests` key
+/// in `kitty.yml` or `problem.yaml`. Settings that are left out fall back to
+/// those in `kitty.yml` and then to the defaults of `TestLayout`.
+#[derive(Debug, Clone, Default)]
+pub struct LayoutSettings {
+    pub dirs: Option<Vec<String>>,
+    pub recursive: Option<bool>,
+    pub input_extensions: Option<Vec<String>>,
+    pub answer_extensions: Option<Vec<String>>,
+}
+
+impl LayoutSettings {
+    /// Reads the settings from the value of a `tests` key. `file` names the
+    /// file they are read from for error messages.
+    pub fn from_yaml(value: &Yaml, file: &str) -> Result<Self, StdErr> {
+        if value.is_badvalue() || value.is_null() {
+            return Ok(Default::default());
+        }
+
+        if value.as_hash().is_none() {
+            return Err(format!("tests in {} must contain a set of keys", file).into());
+        }
+
+        let recursive = match &value["recursive"] {
+            Yaml::Boolean(b) => Some(*b),
+            Yaml::BadValue => None,
+            _ => return Err(format!("tests.recursive in {} must be true or false", file).into()),
+        };
+
+        let extensions = |key: &str| -> Result<Option<Vec<String>>, StdErr> {
+            Ok(string_list(&value[key], key, file)?.map(|e| trim_dots(&e)))
+        };
+
+        Ok(Self {
+            dirs: string_list(&value["dirs"], "dirs", file)?,
+            recursive,
+            input_extensions: extensions("input_extensions")?,
+            answer_extensions: extensions("answer_extensions")?,
+        })
+    }
+
+    /// Gives the settings as the value of a `tests` key, or `None` if none are
+    /// set.
+    pub fn to_yaml(&self) -> Option<Yaml> {
+        let mut hash = Hash::new();
+
+        let lists = [
+            ("dirs", &self.dirs),
+            ("input_extensions", &self.input_extensions),
+            ("answer_extensions", &self.answer_extensions),
+        ];
+
+        for (key, list) in lists.iter() {
+            if let Some(l) = list {
+                hash.insert(
+                    Yaml::String(key.to_string()),
+                    Yaml::Array(l.iter().cloned().map(Yaml::String).collect()),
+                );
+            }
+        }
+
+        if let Some(r) = self.recursive {
+            hash.insert(Yaml::String("recursive".to_string()), Yaml::Boolean(r));
+        }
+
+        if hash.is_empty() {
+            None
+        } else {
+            Some(Yaml::Hash(hash))
+        }
+    }
+}
+
+/// Reads a list of strings, which may also be written as a single string.
+fn string_list(value: &Yaml, key: &str, file: &str) -> Result<Option<Vec<String>>, StdErr> {
+    let error =
+        || -> StdErr { format!("tests.{} in {} must be a list of strings", key, file).into() };
+
+    match value {
+        Yaml::BadValue => Ok(None),
+        Yaml::String(s) => Ok(Some(vec![s.clone()])),
+        Yaml::Array(items) => {
+            let list = items
+                .iter()
+                .map(|i| i.as_str().map(str::to_string))
+                .collect::<Option<Vec<_>>>();
+
+            match list {
+                Some(l) if !l.is_empty() => Ok(Some(l)),
+                _ => Err(error()),
+            }
+        }
+        _ => Err(error()),
+    }
+}
+
+/// Allows extensions to be written as both "in" and ".in".
+fn trim_dots(extensions: &[String]) -> Vec<String> {
+    extensions
+        .iter()
+        .map(|e| e.trim_start_matches('.').to_string())
+        .collect()
+}
+
+/// Where the test cases of a problem are found.
+#[derive(Debug, Clone)]
+pub struct TestLayout {
+    /// The directories holding test cases, relative to the problem directory.
+    pub dirs: Vec<String>,
+    /// Whether test cases in subdirectories are found too.
+    pub recursive: bool,
+    /// The extensions of input files.
+    pub input_extensions: Vec<String>,
+    /// The extensions of answer files, in the order they are looked for.
+    pub answer_extensions: Vec<String>,
+}
+
+impl Default for TestLayout {
+    fn default() -> Self {
+        Self {
+            dirs: vec!["test".to_string()],
+            recursive: false,
+            input_extensions: vec!["in".to_string()],
+            answer_extensions: vec!["ans".to_string()],
+        }
+    }
+}
+
+impl TestLayout {
+    /// The layout of the problem in the given directory, combining the
+    /// settings in its `problem.yaml` with those in `kitty.yml`.
+    pub fn for_problem(problem_dir: &Path) -> Result<Self, StdErr> {
+        let metadata = Metadata::load(problem_dir)?;
+
+        Ok(Self::resolve(&[cfg.test_layout(), &metadata.test_layout]))
+    }
+
+    /// Combines settings, where later ones take precedence.
+    fn resolve(settings: &[&LayoutSettings]) -> Self {
+        let mut layout = Self::default();
+
+        for s in settings {
+            if let Some(d) = &s.dirs {
+                layout.dirs = d.clone();
+            }
+            if let Some(r) = s.recursive {
+                layout.recursive = r;
+            }
+            if let Some(e) = &s.input_extensions {
+                layout.input_extensions = e.clone();
+            }
+            if let Some(e) = &s.answer_extensions {
+                layout.answer_extensions = e.clone();
+            }
+        }
+
+        layout
+    }
+
+    /// Whether the file is part of a test case of the problem in `problem_dir`,
+    /// either by being in one of the test folders or by its extension.
+    pub fn is_test_file(&self, path: &Path, problem_dir: &Path) -> bool {
+        let in_test_dir = self
+            .dirs
+            .iter()
+            .map(String::as_str)
+            .chain(std::iter::once(TEST_DIR_NAME))
+            .any(|d| path.starts_with(problem_dir.join(d)));
+
+        let has_test_extension = match path.extension().and_then(|e| e.to_str()) {
+            Some(ext) => self
+                .input_extensions
+                .iter()
+                .chain(&self.answer_extensions)
+                .any(|e| e.eq_ignore_ascii_case(ext)),
+            None => false,
+        };
+
+        in_test_dir || has_test_extension
+    }
+
+    /// The extension of newly created input files.
+    pub fn input_extension(&self) -> &str {
+        &self.input_extensions[0]
+    }
+
+    /// The extension of newly created answer files.
+    pub fn answer_extension(&self) -> &str {
+        &self.answer_extensions[0]
+    }
+}
@@ -1,5 +1,8 @@
+use regex::Regex;
 use std::{
+    cmp::Ordering,
     io::{self, Write},
+    iter,
     path::Path,
 };
 
@@ -28,3 +31,139 @@ pub fn prompt_bool(question: &str) -> bool {
 
     input.trim().to_lowercase() == "y"
 }
+
+/// Compares names such that numbers within them are ordered by their value,
+/// e.g. "2" before "10" and "sample2" before "sample10". Other characters are
+/// compared without regard to case.
+pub fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let mut a_chunks = chunks(a);
+    let mut b_chunks = chunks(b);
+
+    loop {
+        let (a_chunk, b_chunk) = match (a_chunks.next(), b_chunks.next()) {
+            (Some(x), Some(y)) => (x, y),
+            (None, None) => return a.cmp(b),
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+        };
+
+        let a_is_number = a_chunk.starts_with(|c: char| c.is_ascii_digit());
+        let b_is_number = b_chunk.starts_with(|c: char| c.is_ascii_digit());
+
+        let ordering = if a_is_number && b_is_number {
+            // Comparing the digits without leading zeros by length first
+            // avoids overflowing on long numbers.
+            let x = a_chunk.trim_start_matches('0');
+            let y = b_chunk.trim_start_matches('0');
+            x.len().cmp(&y.len()).then_with(|| x.cmp(y))
+        } else {
+            a_chunk.to_lowercase().cmp(&b_chunk.to_lowercase())
+        };
+
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+}
+
+/// Splits a string into runs of digits and runs of other characters.
+fn chunks(s: &str) -> impl Iterator<Item = &str> {
+    let mut rest = s;
+
+    iter::from_fn(move || {
+        let first = rest.chars().next()?;
+        let end = rest
+            .find(|c: char| c.is_ascii_digit() != first.is_ascii_digit())
+            .unwrap_or(rest.len());
+        let (chunk, tail) = rest.split_at(end);
+        rest = tail;
+        Some(chunk)
+    })
+}
+
+/// Checks whether a name matches a glob pattern, where `*` matches any number
+/// of characters and `?` matches exactly one.
+pub fn glob_matches(pattern: &str, name: &str) -> bool {
+    let mut regex = String::from("^");
+    for c in pattern.chars() {
+        match c {
+            '*' => regex.push_str(".*"),
+            '?' => regex.push('.'),
+            c => regex.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    regex.push('$');
+
+    // The pattern is always valid since everything else is escaped.
+    Regex::new(&regex).unwrap().is_match(name)
+}
+
+/// Hashes bytes with 64-bit FNV-1a. Unlike the hashers of the standard
+/// library, the result stays the same across Rust versions and platforms, so
+/// it can be used in names that are kept on disk.
+pub fn stable_hash(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &b| {
+        (hash ^ u64::from(b)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn natural_cmp_orders_numbers_by_value() {
+        assert_eq!(natural_cmp("2", "10"), Ordering::Less);
+        assert_eq!(natural_cmp("sample10", "sample2"), Ordering::Greater);
+        assert_eq!(natural_cmp("group1/10", "group2/1"), Ordering::Less);
+        assert_eq!(
+            natural_cmp("123456789012345678901234567890", "99999999999999999999"),
+            Ordering::Greater
+        );
+    }
+
+    #[test]
+    fn natural_cmp_breaks_ties() {
+        assert_eq!(natural_cmp("Sample", "sample"), Ordering::Less);
+        assert_eq!(natural_cmp("a", "B"), Ordering::Less);
+        assert_eq!(natural_cmp("07", "7"), Ordering::Less);
+        assert_eq!(natural_cmp("1", "1"), Ordering::Equal);
+        assert_eq!(natural_cmp("1", "1a"), Ordering::Less);
+    }
+
+    #[test]
+    fn natural_cmp_sorts_test_names() {
+        let mut names = vec!["10", "custom", "2", "1", "secret/3", "custom2", "custom10"];
+        names.sort_by(|a, b| natural_cmp(a, b));
+        assert_eq!(
+            names,
+            ["1", "2", "10", "custom", "custom2", "custom10", "secret/3"]
+        );
+    }
+
+    #[test]
+    fn glob_matches_wildcards() {
+        assert!(glob_matches("*", "secret/group1/3"));
+        assert!(glob_matches("custom*", "custom-edge"));
+        assert!(glob_matches("secret/*/3", "secret/group1/3"));
+        assert!(glob_matches("?", "7"));
+        assert!(!glob_matches("?", "10"));
+        assert!(!glob_matches("custom*", "my-custom"));
+    }
+
+    #[test]
+    fn glob_matches_other_characters_literally() {
+        assert!(glob_matches("1", "1"));
+        assert!(!glob_matches("1", "10"));
+        assert!(glob_matches("a.b", "a.b"));
+        assert!(!glob_matches("a.b", "axb"));
+        assert!(glob_matches("(1)+[2]", "(1)+[2]"));
+    }
+
+    #[test]
+    fn stable_hash_is_fnv1a() {
+        assert_eq!(stable_hash(b""), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(stable_hash(b"a"), 0xaf63_dc4c_8601_ec8c);
+        assert_eq!(stable_hash(b"foobar"), 0x8594_4171_f739_67e8);
+    }
+}
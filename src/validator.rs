@@ -0,0 +1,151 @@
+use crate::process::{self, Isolation, ResourceLimits};
+use crate::verdict::{self, Verdict};
+use crate::StdErr;
+use crate::CFG as cfg;
+use std::fs;
+use std::io::{Seek, SeekFrom, Write};
+use std::path::{Path, MAIN_SEPARATOR};
+use std::process::{ExitStatus, Stdio};
+use std::time::Duration;
+
+/// The exit code with which a validator accepts the output.
+pub const EXIT_CODE_ACCEPTED: i32 = 42;
+/// The exit code with which a validator rejects the output.
+const EXIT_CODE_WRONG_ANSWER: i32 = 43;
+
+const VALIDATOR_TIME_LIMIT: Duration = Duration::from_secs(60);
+
+/// A program that decides whether the output of a solution is correct. It is
+/// run like Kattis runs output validators:
+///
+/// ```text
+/// validator input_file judge_answer feedback_dir [flags] < team_output
+/// ```
+///
+/// It accepts the output by exiting with code 42 and rejects it with code 43,
+/// optionally explaining why in `judgemessage.txt` in the feedback directory.
+#[derive(Debug)]
+pub struct Validator {
+    compile_cmd: Option<Vec<String>>,
+    run_cmd: Vec<String>,
+    flags: Vec<String>,
+}
+
+impl Validator {
+    /// Creates a validator from either a path relative to the problem directory
+    /// or a shell command. If the path is a source file in one of the
+    /// configured languages, it is compiled and run like a solution would be.
+    pub fn new(spec: &str, problem_dir: &Path, flags: Vec<String>) -> Result<Self, StdErr> {
+        let path = problem_dir.join(spec);
+
+        if path.is_file() {
+            let lang = match cfg.lang_from_file(&path).ok().flatten() {
+                Some(l) => Some(l.with_profile(None)?),
+                None => None,
+            };
+            let (compile_cmd, run_cmd) = match lang {
+                Some(lang) => (lang.get_compile_cmd(&path)?, lang.get_run_cmd(&path)?),
+                None => (None, vec![path.to_string_lossy().into_owned()]),
+            };
+
+            return Ok(Self {
+                compile_cmd,
+                run_cmd,
+                flags,
+            });
+        }
+
+        match shlex::split(spec) {
+            Some(run_cmd) if !run_cmd.is_empty() => Ok(Self {
+                compile_cmd: None,
+                run_cmd,
+                flags,
+            }),
+            _ => Err(format!("failed to parse validator command \"{}\"", spec).into()),
+        }
+    }
+
+    pub fn compile_cmd(&self) -> Option<&[String]> {
+        self.compile_cmd.as_deref()
+    }
+
+    /// Runs the validator on the output of a solution. Along with the verdict,
+    /// the validator's explanation of a rejection is returned if it gave one.
+    pub fn validate(
+        &self,
+        test_in: &Path,
+        test_ans: &Path,
+        output: &[u8],
+    ) -> Result<(Verdict, Option<String>), StdErr> {
+        let feedback_dir = tempfile::tempdir()?;
+
+        let mut team_output = tempfile::tempfile()?;
+        team_output.write_all(output)?;
+        team_output.seek(SeekFrom::Start(0))?;
+
+        let cmd = self.command(test_in, test_ans, feedback_dir.path());
+        let result = process::run(
+            &cmd,
+            Stdio::from(team_output),
+            VALIDATOR_TIME_LIMIT,
+            &ResourceLimits::default(),
+            &Isolation::default(),
+        )?;
+
+        Ok(judgement(
+            result.status,
+            &result.stderr,
+            feedback_dir.path(),
+        ))
+    }
+
+    /// The command that runs the validator on the given test case.
+    pub fn command(&self, test_in: &Path, test_ans: &Path, feedback_dir: &Path) -> Vec<String> {
+        let mut cmd = self.run_cmd.clone();
+        cmd.push(test_in.to_string_lossy().into_owned());
+        cmd.push(test_ans.to_string_lossy().into_owned());
+        // Validators commonly append file names to the feedback directory
+        // without a separator.
+        cmd.push(format!(
+            "{}{}",
+            feedback_dir.to_string_lossy(),
+            MAIN_SEPARATOR
+        ));
+        cmd.extend(self.flags.iter().cloned());
+        cmd
+    }
+}
+
+/// Turns the exit status of a validator into a verdict along with its judge
+/// message. A status of `None` means that the validator was stopped before it
+/// finished.
+pub fn judgement(
+    status: Option<ExitStatus>,
+    stderr: &[u8],
+    feedback_dir: &Path,
+) -> (Verdict, Option<String>) {
+    let judge_message = fs::read_to_string(feedback_dir.join("judgemessage.txt"))
+        .ok()
+        .map(|m| m.trim().to_string())
+        .filter(|m| !m.is_empty());
+
+    let status = match status {
+        Some(s) => s,
+        None => return (Verdict::JudgeError, Some("validator timed out".to_string())),
+    };
+
+    match status.code() {
+        Some(EXIT_CODE_ACCEPTED) => (Verdict::Accepted, None),
+        Some(EXIT_CODE_WRONG_ANSWER) => (Verdict::WrongAnswer, judge_message),
+        _ => {
+            let stderr = String::from_utf8_lossy(stderr);
+            let message = format!(
+                "validator failed with {}\n{}",
+                verdict::exit_reason(&status),
+                judge_message.as_deref().unwrap_or_else(|| stderr.trim())
+            );
+
+            (Verdict::JudgeError, Some(message))
+        }
+    }
+}
@@ -0,0 +1,93 @@
+use std::fmt;
+use std::process::ExitStatus;
+
+/// The outcome of running a solution on a single test case. The names match
+/// the judgements given by Kattis.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Verdict {
+    Accepted,
+    /// The output only differs from the answer in its whitespace. Like on
+    /// Kattis, this is not a failure, but kitty warns about it.
+    PresentationWarning,
+    WrongAnswer,
+    TimeLimitExceeded,
+    MemoryLimitExceeded,
+    OutputLimitExceeded,
+    RunTimeError,
+    /// The output validator itself failed.
+    JudgeError,
+}
+
+impl Verdict {
+    pub fn is_accepted(self) -> bool {
+        self == Verdict::Accepted
+    }
+
+    /// Whether the test case passed, possibly with a warning.
+    pub fn is_passing(self) -> bool {
+        matches!(self, Verdict::Accepted | Verdict::PresentationWarning)
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Verdict::Accepted => "accepted",
+            Verdict::PresentationWarning => "presentation warning",
+            Verdict::WrongAnswer => "wrong answer",
+            Verdict::TimeLimitExceeded => "time limit exceeded",
+            Verdict::MemoryLimitExceeded => "memory limit exceeded",
+            Verdict::OutputLimitExceeded => "output limit exceeded",
+            Verdict::RunTimeError => "run time error",
+            Verdict::JudgeError => "judge error",
+        }
+    }
+}
+
+impl fmt::Display for Verdict {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.write_str(self.name())
+    }
+}
+
+/// Describes why a program exited, e.g. "exit code 1" or "killed by SIGSEGV
+/// (segmentation fault)".
+pub fn exit_reason(status: &ExitStatus) -> String {
+    if let Some(code) = status.code() {
+        return format!("exit code {}", code);
+    }
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+
+        if let Some(signal) = status.signal() {
+            return match signal_name(signal) {
+                Some((name, description)) => format!("killed by {} ({})", name, description),
+                None => format!("killed by signal {}", signal),
+            };
+        }
+    }
+
+    "unknown exit reason".to_string()
+}
+
+/// Gives the name and a description of the signals a crashing program is
+/// typically killed by.
+#[cfg(unix)]
+fn signal_name(signal: i32) -> Option<(&'static str, &'static str)> {
+    let name = match signal {
+        libc::SIGSEGV => ("SIGSEGV", "segmentation fault"),
+        libc::SIGFPE => ("SIGFPE", "arithmetic error, e.g. division by zero"),
+        libc::SIGABRT => ("SIGABRT", "aborted"),
+        libc::SIGKILL => ("SIGKILL", "killed"),
+        libc::SIGBUS => ("SIGBUS", "bus error"),
+        libc::SIGILL => ("SIGILL", "illegal instruction"),
+        libc::SIGTERM => ("SIGTERM", "terminated"),
+        libc::SIGPIPE => ("SIGPIPE", "broken pipe"),
+        libc::SIGXCPU => ("SIGXCPU", "CPU time limit exceeded"),
+        libc::SIGXFSZ => ("SIGXFSZ", "file size limit exceeded"),
+        libc::SIGSYS => ("SIGSYS", "bad system call, e.g. one blocked by the sandbox"),
+        _ => return None,
+    };
+
+    Some(name)
+}
@@ -0,0 +1,206 @@
+use crate::StdErr;
+use colored::Colorize;
+use notify::{watcher, DebouncedEvent, RecursiveMode, Watcher};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::channel;
+use std::time::Duration;
+
+/// How long to wait for further changes before running again, such that
+/// saving several files at once only causes a single run.
+const DEBOUNCE_DELAY: Duration = Duration::from_millis(500);
+
+/// Directories that never contain anything worth running again for, such as
+/// build output and kitty's own state.
+const IGNORED_DIRS: &[&str] = &[
+    ".git",
+    ".hg",
+    ".svn",
+    ".kitty",
+    ".idea",
+    ".vscode",
+    "target",
+    "node_modules",
+    "__pycache__",
+];
+
+/// Extensions of files written by compilers, editors and kitty itself.
+const IGNORED_EXTENSIONS: &[&str] = &[
+    "o",
+    "obj",
+    "hi",
+    "dyn_o",
+    "dyn_hi",
+    "class",
+    "pyc",
+    "exe",
+    "pdb",
+    "swp",
+    "swo",
+    "swx",
+    "tmp",
+    "transcript",
+];
+
+/// Calls `run` once, and then again every time a file in `dir` or any of its
+/// subdirectories changes. Changes to build output, editor backups and the
+/// files in `ignored` do not count. The screen is cleared before each run.
+///
+/// Since the whole directory is watched rather than single files, editors that
+/// save by writing a temporary file and renaming it over the original are
+/// followed too.
+pub fn watch<F: Fn() -> Result<(), StdErr>>(
+    dir: &Path,
+    ignored: &[PathBuf],
+    run: F,
+) -> Result<(), StdErr> {
+    // Some platforms report changes with the canonical path, which would not
+    // start with `dir` otherwise.
+    let dir = &dir.canonicalize().unwrap_or_else(|_| dir.to_path_buf());
+    let ignored = ignored.iter().map(|p| canonicalize(p)).collect::<Vec<_>>();
+
+    let (tx, rx) = channel();
+    let mut watcher = match watcher(tx, DEBOUNCE_DELAY) {
+        Ok(w) => w,
+        Err(_) => return Err("failed to start watching for file changes".into()),
+    };
+
+    if watcher.watch(dir, RecursiveMode::Recursive).is_err() {
+        return Err(format!("failed to watch {}", dir.display()).into());
+    }
+
+    let run_and_report = |changed: Option<&Path>| {
+        // Clears the screen and moves the cursor to the top left corner.
+        print!("\x1B[2J\x1B[1;1H");
+
+        if let Some(path) = changed {
+            let path = path.strip_prefix(dir).unwrap_or(path);
+            println!("{} {}\n", "changed".bright_cyan(), path.display());
+        }
+
+        match run() {
+            Err(e) if !e.to_string().is_empty() => eprintln!("{}: {}", "error".bright_red(), e),
+            _ => {}
+        }
+
+        println!(
+            "\n{} {} for changes...",
+            "watching".bright_cyan(),
+            dir.file_name()
+                .unwrap_or_else(|| dir.as_os_str())
+                .to_string_lossy()
+                .underline(),
+        );
+        io::stdout().flush().expect("failed to flush stdout");
+    };
+
+    run_and_report(None);
+
+    loop {
+        let event = match rx.recv() {
+            Ok(e) => e,
+            Err(_) => return Err("something went wrong during file watching".into()),
+        };
+
+        let changed = match event {
+            DebouncedEvent::Create(path)
+            | DebouncedEvent::Write(path)
+            | DebouncedEvent::Remove(path) => vec![path],
+            DebouncedEvent::Rename(from, to) => vec![to, from],
+            // Events were lost, so anything may have changed.
+            DebouncedEvent::Rescan => {
+                run_and_report(None);
+                continue;
+            }
+            // The notices come before the events above, and are not needed.
+            _ => continue,
+        };
+
+        if let Some(path) = changed.iter().find(|p| !is_ignored(p, dir, &ignored)) {
+            run_and_report(Some(path));
+        }
+    }
+}
+
+/// Lists the files in `dir` and its subdirectories that `watch` runs again for
+/// when given the same arguments, sorted by path. The paths are canonical, and
+/// directories that cannot be read are skipped.
+pub fn watched_files(dir: &Path, ignored: &[PathBuf]) -> Vec<PathBuf> {
+    let dir = &dir.canonicalize().unwrap_or_else(|_| dir.to_path_buf());
+    let ignored = ignored.iter().map(|p| canonicalize(p)).collect::<Vec<_>>();
+
+    let mut files = Vec::new();
+    collect_watched_files(dir, dir, &ignored, &mut files);
+    files.sort();
+    files
+}
+
+fn collect_watched_files(dir: &Path, root: &Path, ignored: &[PathBuf], files: &mut Vec<PathBuf>) {
+    let entries = match dir.read_dir() {
+        Ok(e) => e,
+        Err(_) => return,
+    };
+
+    for path in entries.filter_map(|e| e.ok()).map(|e| e.path()) {
+        if is_ignored(&path, root, ignored) {
+            continue;
+        }
+
+        if path.is_dir() {
+            collect_watched_files(&path, root, ignored, files);
+        } else {
+            files.push(path);
+        }
+    }
+}
+
+/// Gives the canonical form of a path to a file that may not exist yet, which
+/// the paths of changes can be compared to.
+fn canonicalize(path: &Path) -> PathBuf {
+    let parent = match path.parent() {
+        Some(p) if p != Path::new("") => p,
+        _ => Path::new("."),
+    };
+
+    match (parent.canonicalize(), path.file_name()) {
+        (Ok(parent), Some(name)) => parent.join(name),
+        _ => path.to_path_buf(),
+    }
+}
+
+fn is_ignored(path: &Path, dir: &Path, ignored: &[PathBuf]) -> bool {
+    if ignored.iter().any(|p| p == path) {
+        return true;
+    }
+
+    if let Ok(relative) = path.strip_prefix(dir) {
+        if relative
+            .components()
+            .any(|c| IGNORED_DIRS.iter().any(|d| c.as_os_str() == *d))
+        {
+            return true;
+        }
+    }
+
+    let name = match path.file_name().and_then(|n| n.to_str()) {
+        Some(n) => n,
+        None => return false,
+    };
+
+    // Backups and lock files of Emacs and Vim, and the file Vim writes to check
+    // whether it may create files in a directory.
+    if name.ends_with('~') || name.starts_with(".#") || name.starts_with('#') || name == "4913" {
+        return true;
+    }
+
+    // The program compilers write when not told where to. Other .out files may
+    // be answers.
+    if name == "a.out" {
+        return true;
+    }
+
+    match path.extension().and_then(|e| e.to_str()) {
+        Some(ext) => IGNORED_EXTENSIONS.contains(&ext.to_lowercase().as_str()),
+        None => false,
+    }
+}